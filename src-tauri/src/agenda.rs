@@ -0,0 +1,69 @@
+use chrono::{Datelike, Days, Local, Utc};
+use serde::Serialize;
+use tauri::State;
+
+use crate::db::AppDb;
+use crate::error::{guard, AppError};
+use crate::logging::log_error;
+use crate::models::{Task, TASK_COLUMNS};
+use crate::stats::local_midnight;
+
+/// Open tasks with a due date, by when they're due. Each bucket is sorted by
+/// due date.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct Agenda {
+  /// Due before now.
+  pub overdue: Vec<Task>,
+  /// Due later today.
+  pub today: Vec<Task>,
+  pub tomorrow: Vec<Task>,
+  /// After tomorrow, up to the end of Sunday.
+  pub this_week: Vec<Task>,
+  pub later: Vec<Task>,
+}
+
+/// Open tasks not in the trash or the archive, grouped for the agenda view.
+/// Tasks without a due date aren't on the agenda. Day boundaries are local
+/// midnights, each resolved on its own date, so a DST change in between
+/// doesn't shift them.
+#[tauri::command]
+pub async fn agenda(db: State<'_, AppDb>) -> Result<Agenda, AppError> {
+  guard("agenda", async {
+    let now = Utc::now();
+    let today = now.with_timezone(&Local).date_naive();
+    let tomorrow = local_midnight(today + Days::new(1));
+    let day_after = local_midnight(today + Days::new(2));
+    let next_week =
+      local_midnight(today + Days::new(7 - u64::from(today.weekday().num_days_from_monday())));
+
+    let tasks = sqlx::query_as::<_, Task>(&format!(
+      "SELECT {TASK_COLUMNS} FROM tasks \
+       WHERE done = 0 AND deleted_at IS NULL AND archived = 0 AND due IS NOT NULL \
+       ORDER BY due ASC, id ASC;"
+    ))
+    .fetch_all(&db.pool())
+    .await
+    .map_err(log_error("agenda"))?;
+
+    let mut agenda = Agenda::default();
+    for task in tasks {
+      let Some(due) = task.due else {
+        continue;
+      };
+      let bucket = if due < now {
+        &mut agenda.overdue
+      } else if due < tomorrow {
+        &mut agenda.today
+      } else if due < day_after {
+        &mut agenda.tomorrow
+      } else if due < next_week {
+        &mut agenda.this_week
+      } else {
+        &mut agenda.later
+      };
+      bucket.push(task);
+    }
+    Ok(agenda)
+  })
+  .await
+}
@@ -0,0 +1,127 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use tauri::{AppHandle, State};
+
+use crate::db::{with_retry, AppDb};
+use crate::error::{guard, AppError};
+use crate::events::{self, ChangeKind};
+use crate::history::{History, Recorder};
+use crate::logging::log_error;
+use crate::models::timestamp;
+
+pub(crate) const ATTACHMENT_COLUMNS: &str =
+  "id, task_id, file_name, path, size, modified_at, attached_at";
+
+/// A link from a task to a file on disk. Only the reference is stored: the
+/// file isn't copied, and deleting the task (which drops these rows) leaves
+/// it where it is.
+#[derive(Serialize, Deserialize, FromRow, Clone, Debug)]
+pub struct Attachment {
+  pub id: i64,
+  pub task_id: i64,
+  /// Name of the file when it was attached.
+  pub file_name: String,
+  /// Absolute path it was attached from.
+  pub path: String,
+  /// Size in bytes and last modification time at attach time.
+  pub size: i64,
+  pub modified_at: Option<DateTime<Utc>>,
+  pub attached_at: DateTime<Utc>,
+}
+
+/// What `add_attachment` does, minus the change event; takes the pool
+/// directly, so it runs without an app (integration tests).
+pub async fn add(
+  db: &AppDb,
+  history: &History,
+  task_id: i64,
+  path: &str,
+) -> Result<Attachment, AppError> {
+  let path = fs::canonicalize(PathBuf::from(path.trim()))
+    .map_err(|e| AppError::Validation(format!("cannot attach {path:?}: {e}")))?;
+  let unreadable =
+    |e: std::io::Error| AppError::Validation(format!("cannot attach {}: {e}", path.display()));
+  let meta = fs::metadata(&path).map_err(unreadable)?;
+  if !meta.is_file() {
+    return Err(AppError::Validation(format!(
+      "cannot attach {}: not a file",
+      path.display()
+    )));
+  }
+  File::open(&path).map_err(unreadable)?;
+  let file_name = path
+    .file_name()
+    .map(|n| n.to_string_lossy().into_owned())
+    .unwrap_or_default();
+  let modified_at = meta.modified().ok().map(DateTime::<Utc>::from);
+
+  let now = Utc::now();
+  let (attachment, entry) = with_retry("add_attachment", || async {
+    let mut tx = db.pool().begin().await?;
+    let undo = Recorder::start(&mut tx, "add_attachment", &[task_id]).await?;
+    let attachment = sqlx::query_as::<_, Attachment>(&format!(
+      "INSERT INTO attachments (task_id, file_name, path, size, modified_at, attached_at) \
+       SELECT id, ?, ?, ?, ?, ? FROM tasks WHERE id = ? AND deleted_at IS NULL \
+       RETURNING {ATTACHMENT_COLUMNS};"
+    ))
+    .bind(&file_name)
+    .bind(path.to_string_lossy().into_owned())
+    .bind(i64::try_from(meta.len()).unwrap_or(i64::MAX))
+    .bind(modified_at.as_ref().map(timestamp))
+    .bind(timestamp(&now))
+    .bind(task_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound)?;
+    sqlx::query("UPDATE tasks SET updated_at = ? WHERE id = ?;")
+      .bind(timestamp(&now))
+      .bind(task_id)
+      .execute(&mut *tx)
+      .await?;
+    let entry = undo.finish(&mut tx).await?;
+    tx.commit().await?;
+    Ok((attachment, entry))
+  })
+  .await?;
+  history.record(entry);
+  Ok(attachment)
+}
+
+/// Links the file at `path` to a task. The file has to exist and be readable
+/// now; it isn't checked again later.
+#[tauri::command]
+pub async fn add_attachment(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  task_id: i64,
+  path: String,
+) -> Result<Attachment, AppError> {
+  guard("add_attachment", async {
+    let attachment = add(&db, &history, task_id, &path).await?;
+    events::task_changed(&app, task_id, ChangeKind::Updated);
+    Ok(attachment)
+  })
+  .await
+}
+
+/// A task's attachments, oldest first.
+#[tauri::command]
+pub async fn list_attachments(
+  db: State<'_, AppDb>,
+  task_id: i64,
+) -> Result<Vec<Attachment>, AppError> {
+  guard("list_attachments", async {
+    sqlx::query_as::<_, Attachment>(&format!(
+      "SELECT {ATTACHMENT_COLUMNS} FROM attachments WHERE task_id = ? ORDER BY attached_at, id;"
+    ))
+    .bind(task_id)
+    .fetch_all(&db.pool())
+    .await
+    .map_err(log_error("list_attachments"))
+  })
+  .await
+}
@@ -0,0 +1,424 @@
+use chrono::{DateTime, NaiveDateTime, SubsecRound, TimeDelta, Utc};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::async_runtime::{self, JoinHandle};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{is_corruption, AppDb};
+use crate::encryption::remove_if_exists;
+use crate::error::{guard, AppError};
+use crate::events::{self, ChangeKind};
+use crate::history::History;
+use crate::logging::log_error;
+use crate::migrations;
+use crate::notes::Notes;
+use crate::reminders::Notified;
+
+/// How old the newest snapshot gets before the background job writes another;
+/// it sleeps until then, so restarting the app neither adds one nor pushes the
+/// next one back.
+const INTERVAL: TimeDelta = TimeDelta::days(1);
+
+/// How long the background job waits after writing a snapshot (or failing
+/// to) before it looks again, so a failure isn't retried in a tight loop.
+const RETRY: Duration = Duration::from_secs(60 * 60);
+
+/// Snapshots kept in `backups/`; older ones are deleted after each backup.
+const KEEP: usize = 7;
+
+const PREFIX: &str = "tasks.";
+const SUFFIX: &str = ".db";
+const STAMP: &str = "%Y%m%dT%H%M%SZ";
+/// Between the stamp and a counter, for snapshots taken within the same second
+/// as an earlier one: `tasks.<stamp>-2.db`.
+const COUNTER: char = '-';
+
+/// One snapshot in the `backups/` folder.
+#[derive(Serialize, Clone, Debug)]
+pub struct Backup {
+  pub name: String,
+  pub path: String,
+  /// Size in bytes.
+  pub size: i64,
+  /// When it was taken, from its file name.
+  pub created_at: DateTime<Utc>,
+}
+
+/// Handle to the backup loop; `stop` it when the app exits.
+pub struct Backups(JoinHandle<()>);
+
+impl Backups {
+  pub fn stop(&self) {
+    self.0.abort();
+  }
+}
+
+pub fn spawn(app: &AppHandle) -> Backups {
+  let app = app.clone();
+  Backups(async_runtime::spawn(async move {
+    loop {
+      let db = app.state::<AppDb>();
+      let due_in = match list(&dir(&db)) {
+        // already past due when negative, which `to_std` rejects
+        Ok(backups) => backups.first().map_or(Duration::ZERO, |newest| {
+          (newest.created_at + INTERVAL - Utc::now())
+            .to_std()
+            .unwrap_or(Duration::ZERO)
+        }),
+        Err(e) => {
+          log::warn!("could not list backups: {e}");
+          Duration::ZERO
+        }
+      };
+      if !due_in.is_zero() {
+        // and then look again, in case `backup_now` took one meanwhile
+        tokio::time::sleep(due_in).await;
+        continue;
+      }
+      match run(&db).await {
+        Ok(backup) => log::info!("backed up the database to {}", backup.path),
+        Err(e) => log::error!("scheduled backup failed: {e}"),
+      }
+      tokio::time::sleep(RETRY).await;
+    }
+  }))
+}
+
+/// `backups/` next to the database file, i.e. under the app data dir.
+fn dir(db: &AppDb) -> PathBuf {
+  let file = db.pool().connect_options().get_filename().to_path_buf();
+  file.parent().unwrap_or(Path::new(".")).join("backups")
+}
+
+/// Every snapshot in `dir`, newest first; files that don't look like one are
+/// left out.
+fn list(dir: &Path) -> io::Result<Vec<Backup>> {
+  let entries = match fs::read_dir(dir) {
+    Ok(entries) => entries,
+    Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+    Err(e) => return Err(e),
+  };
+  let mut backups = Vec::new();
+  for entry in entries {
+    let entry = entry?;
+    let name = entry.file_name().to_string_lossy().into_owned();
+    let Some(stamp) = name
+      .strip_prefix(PREFIX)
+      .and_then(|rest| rest.strip_suffix(SUFFIX))
+    else {
+      continue;
+    };
+    let (stamp, count) = match stamp.split_once(COUNTER) {
+      Some((stamp, count)) => match count.parse::<u32>() {
+        Ok(count) => (stamp, count),
+        Err(_) => continue,
+      },
+      None => (stamp, 1),
+    };
+    let Ok(created_at) = NaiveDateTime::parse_from_str(stamp, STAMP) else {
+      continue;
+    };
+    let meta = entry.metadata()?;
+    if !meta.is_file() {
+      continue;
+    }
+    let backup = Backup {
+      path: entry.path().to_string_lossy().into_owned(),
+      name,
+      size: i64::try_from(meta.len()).unwrap_or(i64::MAX),
+      created_at: created_at.and_utc(),
+    };
+    backups.push((count, backup));
+  }
+  backups.sort_by_key(|(count, b)| Reverse((b.created_at, *count)));
+  Ok(backups.into_iter().map(|(_, b)| b).collect())
+}
+
+/// Writes a snapshot with `VACUUM INTO`, which reads the whole database inside
+/// one read transaction: writers carry on meanwhile, and the copy is the state
+/// as of its start rather than a file caught mid-write. It's written under a
+/// temporary name first, so an interrupted backup never shows up in the list.
+/// An encrypted database is copied encrypted, with the same passphrase.
+async fn run(db: &AppDb) -> Result<Backup, AppError> {
+  let dir = dir(db);
+  fs::create_dir_all(&dir).map_err(log_error("backup"))?;
+  // the name only has whole seconds; match what `list` will report
+  let created_at = Utc::now().trunc_subsecs(0);
+  let stamp = created_at.format(STAMP).to_string();
+  let mut name = format!("{PREFIX}{stamp}{SUFFIX}");
+  // another backup finished within the same second, e.g. `backup_now` right
+  // before `restore_backup` takes its own
+  let mut count = 1;
+  while dir.join(&name).exists() {
+    count += 1;
+    name = format!("{PREFIX}{stamp}{COUNTER}{count}{SUFFIX}");
+  }
+  let path = dir.join(&name);
+  let partial = dir.join(format!("{name}.partial"));
+  if partial.exists() {
+    fs::remove_file(&partial).map_err(log_error("backup"))?;
+  }
+
+  sqlx::query("VACUUM INTO ?;")
+    .bind(partial.to_string_lossy().into_owned())
+    .execute(&db.pool())
+    .await
+    .map_err(log_error("backup"))?;
+  fs::rename(&partial, &path).map_err(log_error("backup"))?;
+  let size = fs::metadata(&path).map_err(log_error("backup"))?.len();
+
+  match list(&dir) {
+    Ok(backups) => {
+      for old in backups.iter().skip(KEEP) {
+        if let Err(e) = fs::remove_file(&old.path) {
+          log::warn!("could not remove old backup {}: {e}", old.path);
+        }
+      }
+    }
+    Err(e) => log::warn!("could not prune backups: {e}"),
+  }
+
+  Ok(Backup {
+    path: path.to_string_lossy().into_owned(),
+    name,
+    size: i64::try_from(size).unwrap_or(i64::MAX),
+    created_at,
+  })
+}
+
+/// Takes a snapshot now, on top of the daily one; the oldest beyond the last
+/// seven is removed.
+#[tauri::command]
+pub async fn backup_now(db: State<'_, AppDb>) -> Result<Backup, AppError> {
+  guard("backup_now", async { run(&db).await }).await
+}
+
+/// Snapshots available to restore from, newest first.
+#[tauri::command]
+pub async fn list_backups(db: State<'_, AppDb>) -> Result<Vec<Backup>, AppError> {
+  guard("list_backups", async {
+    list(&dir(&db)).map_err(log_error("list_backups"))
+  })
+  .await
+}
+
+/// Where `restore_backup` stages the snapshot that replaces the database.
+fn restore_path(path: &Path) -> PathBuf {
+  let mut staged = path.as_os_str().to_owned();
+  staged.push(".restore");
+  PathBuf::from(staged)
+}
+
+/// Where `restore_file` keeps the database while the snapshot that replaces
+/// it is opened, so it can be put back should that fail.
+fn aside_path(path: &Path) -> PathBuf {
+  let mut aside = path.as_os_str().to_owned();
+  aside.push(".pre-restore");
+  PathBuf::from(aside)
+}
+
+/// `path` with `suffix` appended, e.g. the `-wal` and `-shm` files SQLite
+/// keeps next to a database.
+fn sidecar(path: &Path, suffix: &str) -> PathBuf {
+  let mut side = path.as_os_str().to_owned();
+  side.push(suffix);
+  PathBuf::from(side)
+}
+
+/// Moves a database and its `-wal`/`-shm` files, which would be replayed into
+/// whatever replaces it, to `to`.
+fn move_db(from: &Path, to: &Path) -> io::Result<()> {
+  fs::rename(from, to)?;
+  for suffix in ["-wal", "-shm"] {
+    let side = sidecar(from, suffix);
+    if side.exists() {
+      fs::rename(&side, sidecar(to, suffix))?;
+    }
+  }
+  Ok(())
+}
+
+/// Removes a database and its `-wal`/`-shm` files, if there are any.
+fn remove_db(path: &Path) -> io::Result<()> {
+  remove_if_exists(path)?;
+  for suffix in ["-wal", "-shm"] {
+    remove_if_exists(&sidecar(path, suffix))?;
+  }
+  Ok(())
+}
+
+/// Undoes a `restore_backup` the app quit in the middle of: the staged
+/// snapshot is dropped, and the database moved aside for it put back. Must
+/// run while nothing has the database open.
+pub fn recover_restore(path: &Path) -> io::Result<()> {
+  remove_if_exists(&restore_path(path))?;
+  let aside = aside_path(path);
+  if !aside.exists() {
+    return Ok(());
+  }
+  remove_db(path)?;
+  move_db(&aside, path)
+}
+
+/// What `restore_backup` does once the snapshot is checked and the database
+/// backed up, minus the SQL plugin's pool and the change events: the snapshot
+/// is copied next to the database, the database moved aside for it, and the
+/// copy opened (and migrated, if it's older). Should that fail, the database
+/// is moved back and reopened as it was. Takes the pool directly, so it runs
+/// without an app (integration tests).
+pub async fn restore_file(db: &AppDb, snapshot: &Path) -> Result<(), AppError> {
+  let current = db.pool().connect_options().get_filename().to_owned();
+  // copied under a temporary name, so a partial copy is never swapped in
+  let staged = restore_path(&current);
+  let partial = sidecar(&staged, ".partial");
+  fs::copy(snapshot, &partial).map_err(log_error("restore_backup"))?;
+  fs::rename(&partial, &staged).map_err(log_error("restore_backup"))?;
+
+  let aside = aside_path(&current);
+  db.reopen(
+    |path| {
+      move_db(path, &aside)?;
+      fs::rename(&staged, path)
+    },
+    |path| {
+      remove_if_exists(&staged)?;
+      if aside.exists() {
+        remove_db(path)?;
+        move_db(&aside, path)?;
+      }
+      Ok(())
+    },
+  )
+  .await?;
+  // only once the snapshot is open, which is when it can't be moved back
+  if let Err(e) = remove_db(&aside) {
+    log::warn!("could not remove {}: {e}", aside.display());
+  }
+  Ok(())
+}
+
+/// Schema version of the database at `path`. It's attached to one of the
+/// pool's connections, so an encrypted snapshot is read with the current key.
+async fn version_of(db: &AppDb, path: &Path) -> Result<i64, AppError> {
+  let unreadable = || AppError::Validation(format!("{} is not a Tasks backup", path.display()));
+  let mut conn = db
+    .pool()
+    .acquire()
+    .await
+    .map_err(log_error("restore_backup"))?;
+  sqlx::query("ATTACH DATABASE ? AS candidate;")
+    .bind(path.to_string_lossy().into_owned())
+    .execute(&mut *conn)
+    .await
+    .map_err(log_error("restore_backup"))?;
+  let read = async {
+    let check: String = sqlx::query_scalar("PRAGMA candidate.quick_check;")
+      .fetch_one(&mut *conn)
+      .await?;
+    if check != "ok" {
+      return Ok(None);
+    }
+    let migrated: i64 = sqlx::query_scalar(
+      "SELECT COUNT(*) FROM candidate.sqlite_master \
+       WHERE type = 'table' AND name = '_sqlx_migrations';",
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    if migrated == 0 {
+      return Ok(None);
+    }
+    sqlx::query_scalar::<_, Option<i64>>(
+      "SELECT MAX(version) FROM candidate._sqlx_migrations WHERE success = 1;",
+    )
+    .fetch_one(&mut *conn)
+    .await
+  }
+  .await;
+  sqlx::query("DETACH DATABASE candidate;")
+    .execute(&mut *conn)
+    .await
+    .map_err(log_error("restore_backup"))?;
+
+  match read {
+    Ok(Some(version)) => Ok(version),
+    Ok(None) => Err(unreadable()),
+    // not SQLite at all, or encrypted with another key
+    Err(e) if is_corruption(&e) => Err(unreadable()),
+    Err(e) => Err(log_error("restore_backup")(e)),
+  }
+}
+
+/// Replaces every task, list and tag with the snapshot at `path`, which has
+/// to be an intact Tasks database no newer than this build. Pending notes are
+/// written and the database as it was is kept as a backup first, and it's
+/// left untouched if either fails. Then every connection is closed and the
+/// snapshot swapped in (see `restore_file`); should it fail to open, the
+/// database is put back as it was. Undo history and sent reminders belong to the old
+/// data, so they're forgotten, and windows are told every task changed.
+#[tauri::command]
+pub async fn restore_backup(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  notified: State<'_, Notified>,
+  path: String,
+) -> Result<(), AppError> {
+  guard("restore_backup", async {
+    let path = fs::canonicalize(PathBuf::from(path.trim()))
+      .map_err(|e| AppError::Validation(format!("cannot restore {path:?}: {e}")))?;
+    if !path.is_file() {
+      return Err(AppError::Validation(format!(
+        "cannot restore {}: not a file",
+        path.display()
+      )));
+    }
+    let current = db.pool().connect_options().get_filename().to_owned();
+    if fs::canonicalize(&current).is_ok_and(|current| current == path) {
+      return Err(AppError::validation("that is the database already in use"));
+    }
+
+    let version = version_of(&db, &path).await?;
+    let supported = migrations::latest_version();
+    if version > supported {
+      return Err(AppError::Validation(format!(
+        "{} is from a newer version of Tasks (schema {version}, this one supports up to {supported})",
+        path.display()
+      )));
+    }
+
+    // into the backup below, rather than into the restored database
+    app.state::<Notes>().flush_all(&app).await;
+    // so the restore itself can be undone by restoring this one
+    run(&db).await?;
+    let task_ids = "SELECT id FROM tasks ORDER BY id;";
+    let before: Vec<i64> = sqlx::query_scalar(task_ids)
+      .fetch_all(&db.pool())
+      .await
+      .map_err(log_error("restore_backup"))?;
+
+    log::info!("restoring {} (schema {version})", path.display());
+    db.unshare(&app).await;
+    let restored = restore_file(&db, &path).await;
+    db.reshare(&app).await;
+    restored?;
+    history.clear();
+    notified.0.lock().unwrap().clear();
+
+    let after: Vec<i64> = sqlx::query_scalar(task_ids)
+      .fetch_all(&db.pool())
+      .await
+      .map_err(log_error("restore_backup"))?;
+    let gone = before
+      .into_iter()
+      .filter(|id| after.binary_search(id).is_err())
+      .collect();
+    events::tasks_changed(&app, gone, ChangeKind::Deleted);
+    events::tasks_changed(&app, after, ChangeKind::Updated);
+    Ok(())
+  })
+  .await
+}
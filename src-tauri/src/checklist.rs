@@ -0,0 +1,197 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqliteConnection};
+use tauri::{AppHandle, State};
+
+use crate::db::{with_retry, AppDb, TxError};
+use crate::error::{guard, AppError};
+use crate::events::{self, ChangeKind};
+use crate::history::{History, Recorder};
+use crate::models::timestamp;
+
+const CHECKLIST_COLUMNS: &str = "id, task_id, text, done, position";
+
+/// A step within a task, ticked off on its own without being a subtask.
+#[derive(Serialize, Deserialize, FromRow, Clone, Debug)]
+pub struct ChecklistItem {
+  pub id: i64,
+  pub task_id: i64,
+  pub text: String,
+  pub done: bool,
+  /// 0-based, contiguous within the task.
+  pub position: i64,
+}
+
+/// A task's checklist, in order.
+pub(crate) async fn items(
+  conn: &mut SqliteConnection,
+  task_id: i64,
+) -> sqlx::Result<Vec<ChecklistItem>> {
+  sqlx::query_as::<_, ChecklistItem>(&format!(
+    "SELECT {CHECKLIST_COLUMNS} FROM checklist_items WHERE task_id = ? ORDER BY position, id;"
+  ))
+  .bind(task_id)
+  .fetch_all(&mut *conn)
+  .await
+}
+
+/// The task an item belongs to.
+async fn task_of(conn: &mut SqliteConnection, item_id: i64) -> Result<i64, TxError> {
+  sqlx::query_scalar("SELECT task_id FROM checklist_items WHERE id = ?;")
+    .bind(item_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| AppError::NotFound.into())
+}
+
+/// Checklist edits count as edits of the task itself.
+async fn touch(conn: &mut SqliteConnection, task_id: i64) -> sqlx::Result<()> {
+  sqlx::query("UPDATE tasks SET updated_at = ? WHERE id = ?;")
+    .bind(timestamp(&Utc::now()))
+    .bind(task_id)
+    .execute(&mut *conn)
+    .await?;
+  Ok(())
+}
+
+/// What `add_checklist_item` does, minus the change event; takes the pool
+/// directly, so it runs without an app (integration tests).
+pub async fn add(
+  db: &AppDb,
+  history: &History,
+  task_id: i64,
+  text: &str,
+) -> Result<ChecklistItem, AppError> {
+  let text = text.trim();
+  if text.is_empty() {
+    return Err(AppError::validation("checklist item must not be empty"));
+  }
+
+  let (item, entry) = with_retry("add_checklist_item", || async {
+    let mut tx = db.pool().begin().await?;
+    let undo = Recorder::start(&mut tx, "add_checklist_item", &[task_id]).await?;
+    let item = sqlx::query_as::<_, ChecklistItem>(&format!(
+      "INSERT INTO checklist_items (task_id, text, done, position) \
+       SELECT id, ?, 0, \
+         (SELECT COALESCE(MAX(position) + 1, 0) FROM checklist_items WHERE task_id = tasks.id) \
+       FROM tasks WHERE id = ? AND deleted_at IS NULL \
+       RETURNING {CHECKLIST_COLUMNS};"
+    ))
+    .bind(text)
+    .bind(task_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound)?;
+    touch(&mut tx, task_id).await?;
+    let entry = undo.finish(&mut tx).await?;
+    tx.commit().await?;
+    Ok((item, entry))
+  })
+  .await?;
+  history.record(entry);
+  Ok(item)
+}
+
+/// Appends an unticked item to the end of a task's checklist.
+#[tauri::command]
+pub async fn add_checklist_item(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  task_id: i64,
+  text: String,
+) -> Result<ChecklistItem, AppError> {
+  guard("add_checklist_item", async {
+    let item = add(&db, &history, task_id, &text).await?;
+    events::task_changed(&app, task_id, ChangeKind::Updated);
+    Ok(item)
+  })
+  .await
+}
+
+/// Ticks an item off, or back on; returns it as it is now.
+#[tauri::command]
+pub async fn toggle_checklist_item(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  item_id: i64,
+) -> Result<ChecklistItem, AppError> {
+  guard("toggle_checklist_item", async {
+    let (item, entry) = with_retry("toggle_checklist_item", || async {
+      let mut tx = db.pool().begin().await?;
+      let task_id = task_of(&mut tx, item_id).await?;
+      let undo = Recorder::start(&mut tx, "toggle_checklist_item", &[task_id]).await?;
+      let item = sqlx::query_as::<_, ChecklistItem>(&format!(
+        "UPDATE checklist_items SET done = NOT done WHERE id = ? RETURNING {CHECKLIST_COLUMNS};"
+      ))
+      .bind(item_id)
+      .fetch_optional(&mut *tx)
+      .await?
+      .ok_or(AppError::NotFound)?;
+      touch(&mut tx, item.task_id).await?;
+      let entry = undo.finish(&mut tx).await?;
+      tx.commit().await?;
+      Ok((item, entry))
+    })
+    .await?;
+    history.record(entry);
+
+    events::task_changed(&app, item.task_id, ChangeKind::Updated);
+    Ok(item)
+  })
+  .await
+}
+
+/// Moves an item to `position` in its checklist (past the end means last),
+/// shifting the others; returns the whole checklist in its new order.
+#[tauri::command]
+pub async fn reorder_checklist_item(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  item_id: i64,
+  position: i64,
+) -> Result<Vec<ChecklistItem>, AppError> {
+  guard("reorder_checklist_item", async {
+    if position < 0 {
+      return Err(AppError::validation("position must not be negative"));
+    }
+
+    let (task_id, reordered, entry) = with_retry("reorder_checklist_item", || async {
+      let mut tx = db.pool().begin().await?;
+      let task_id = task_of(&mut tx, item_id).await?;
+      let undo = Recorder::start(&mut tx, "reorder_checklist_item", &[task_id]).await?;
+
+      let mut ids: Vec<i64> = items(&mut tx, task_id)
+        .await?
+        .into_iter()
+        .map(|item| item.id)
+        .filter(|&id| id != item_id)
+        .collect();
+      let at = usize::try_from(position)
+        .unwrap_or(usize::MAX)
+        .min(ids.len());
+      ids.insert(at, item_id);
+      // renumbered from scratch, so positions stay 0..n whatever they were
+      for (index, id) in ids.iter().enumerate() {
+        sqlx::query("UPDATE checklist_items SET position = ? WHERE id = ?;")
+          .bind(index as i64)
+          .bind(id)
+          .execute(&mut *tx)
+          .await?;
+      }
+      touch(&mut tx, task_id).await?;
+      let reordered = items(&mut tx, task_id).await?;
+      let entry = undo.finish(&mut tx).await?;
+      tx.commit().await?;
+      Ok((task_id, reordered, entry))
+    })
+    .await?;
+    history.record(entry);
+
+    events::task_changed(&app, task_id, ChangeKind::Updated);
+    Ok(reordered)
+  })
+  .await
+}
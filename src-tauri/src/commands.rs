@@ -0,0 +1,1494 @@
+use chrono::{DateTime, Days, Local, Utc};
+use sqlx::{QueryBuilder, Sqlite, SqliteConnection};
+use tauri::{AppHandle, State};
+
+use crate::checklist;
+use crate::db::{with_retry, AppDb, TxError};
+use crate::due;
+use crate::error::{guard, AppError};
+use crate::events::{self, ChangeKind};
+use crate::history::{History, Recorder};
+use crate::logging::log_error;
+use crate::models::{
+  timestamp, NewTask, PagedTasks, Priority, RepeatRule, SnoozeSpec, SortBy, Task, TaskFilter,
+  TaskPatch, Toggled, TASK_COLUMNS, TASK_TAGS,
+};
+use crate::notes::Notes;
+use crate::reminders::Notified;
+use crate::stats::local_midnight;
+use crate::streak;
+
+pub(crate) fn parse_timestamp(field: &str, value: &str) -> Result<DateTime<Utc>, AppError> {
+  DateTime::parse_from_rfc3339(value.trim())
+    .map(|dt| dt.with_timezone(&Utc))
+    .map_err(|e| {
+      AppError::Validation(format!(
+        "invalid {field} {value:?}: expected an RFC3339 timestamp ({e})"
+      ))
+    })
+}
+
+/// `?, ?, ?` for an `IN (...)` list of `n` bound values.
+pub(crate) fn placeholders(n: usize) -> String {
+  vec!["?"; n].join(", ")
+}
+
+/// Turns free text into an FTS5 query: every word must match, as a prefix.
+/// Only alphanumeric runs are kept, so user input can't inject FTS syntax.
+fn fts_query(input: &str) -> Option<String> {
+  let terms: Vec<String> = input
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|t| !t.is_empty())
+    .map(|t| format!("\"{t}\"*"))
+    .collect();
+  (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// Every task below `parent` (children, grandchildren, ...). `UNION` rather
+/// than `UNION ALL` keeps the recursion finite even on a corrupt cycle.
+const DESCENDANTS: &str = "WITH RECURSIVE descendants(id) AS ( \
+     SELECT id FROM tasks WHERE parent_id = ?1 \
+     UNION SELECT t.id FROM tasks t JOIN descendants d ON t.parent_id = d.id \
+   )";
+
+pub(crate) async fn descendants(conn: &mut SqliteConnection, id: i64) -> sqlx::Result<Vec<i64>> {
+  sqlx::query_scalar(&format!("{DESCENDANTS} SELECT id FROM descendants;"))
+    .bind(id)
+    .fetch_all(&mut *conn)
+    .await
+}
+
+/// Rejects `parent_id` for task `id` if the parent is missing, or if it would
+/// make the task its own ancestor.
+async fn check_parent(
+  conn: &mut SqliteConnection,
+  id: Option<i64>,
+  parent_id: i64,
+) -> Result<(), TxError> {
+  let exists: Option<i64> = sqlx::query_scalar("SELECT id FROM tasks WHERE id = ?;")
+    .bind(parent_id)
+    .fetch_optional(&mut *conn)
+    .await?;
+  if exists.is_none() {
+    return Err(AppError::validation("parent task not found").into());
+  }
+  let Some(id) = id else {
+    return Ok(());
+  };
+
+  let cycle: bool = sqlx::query_scalar(
+    "WITH RECURSIVE ancestors(id) AS ( \
+       SELECT ?1 \
+       UNION SELECT t.parent_id FROM tasks t JOIN ancestors a ON t.id = a.id \
+       WHERE t.parent_id IS NOT NULL \
+     ) \
+     SELECT EXISTS (SELECT 1 FROM ancestors WHERE id = ?2);",
+  )
+  .bind(parent_id)
+  .bind(id)
+  .fetch_one(&mut *conn)
+  .await?;
+  if cycle {
+    return Err(
+      AppError::validation("a task cannot be nested under itself or its own subtasks").into(),
+    );
+  }
+  Ok(())
+}
+
+#[tauri::command]
+pub fn db_url(db: State<'_, AppDb>) -> String {
+  db.url()
+}
+
+/// What `create_task` does, minus the change event; takes the pool directly,
+/// so it runs without an app (integration tests).
+pub async fn create(db: &AppDb, history: &History, new: NewTask) -> Result<Task, AppError> {
+  let NewTask {
+    title,
+    due,
+    repeat,
+    priority,
+    parent_id,
+    list_id,
+  } = new;
+  let title = title.trim();
+  if title.is_empty() {
+    return Err(AppError::validation("title must not be empty"));
+  }
+  let due = due.map(|d| parse_timestamp("due", &d)).transpose()?;
+
+  let now = timestamp(&Utc::now());
+  let (task, entry) = with_retry("create_task", || async {
+    let mut tx = db.pool().begin().await?;
+    if let Some(parent_id) = parent_id {
+      check_parent(&mut tx, None, parent_id).await?;
+    }
+    let task = sqlx::query_as::<_, Task>(&format!(
+      "INSERT INTO tasks \
+         (title, done, created_at, updated_at, due, repeat, priority, parent_id, list_id, \
+          sort_order) \
+       VALUES (?, 0, ?, ?, ?, ?, ?, ?, ?, (SELECT COALESCE(MAX(sort_order), 0) + 1 FROM tasks)) \
+       RETURNING {TASK_COLUMNS};"
+    ))
+    .bind(title)
+    .bind(&now)
+    .bind(&now)
+    .bind(due.as_ref().map(timestamp))
+    .bind(RepeatRule::to_column(repeat))
+    .bind(priority.unwrap_or_default().to_column())
+    .bind(parent_id)
+    .bind(list_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let mut undo = Recorder::start(&mut tx, "create_task", &[]).await?;
+    undo.created(task.id);
+    let entry = undo.finish(&mut tx).await?;
+    tx.commit().await?;
+    Ok((task, entry))
+  })
+  .await?;
+  history.record(entry);
+  Ok(task)
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_task(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  title: String,
+  due: Option<String>,
+  repeat: Option<RepeatRule>,
+  priority: Option<Priority>,
+  parent_id: Option<i64>,
+  list_id: Option<i64>,
+) -> Result<Task, AppError> {
+  guard("create_task", async {
+    let new = NewTask {
+      title,
+      due,
+      repeat,
+      priority,
+      parent_id,
+      list_id,
+    };
+    let task = create(&db, &history, new).await?;
+    events::task_changed(&app, task.id, ChangeKind::Created);
+    Ok(task)
+  })
+  .await
+}
+
+/// Rows `list_tasks` shows for a filter, binding `TaskFilter::done` as `?1`,
+/// `TaskFilter::archived` as `?2` and the optional list as `?3`.
+const LISTED: &str = "deleted_at IS NULL AND (?1 IS NULL OR done = ?1) AND archived = ?2 \
+   AND (?3 IS NULL OR list_id = ?3)";
+
+/// Largest page `list_tasks_paged` returns, whatever the caller asks for.
+const MAX_PAGE_SIZE: u32 = 500;
+
+/// `list_tasks` with the pool passed in.
+pub async fn list(
+  db: &AppDb,
+  filter: TaskFilter,
+  sort: SortBy,
+  list_id: Option<i64>,
+  with_tags: bool,
+) -> Result<Vec<Task>, AppError> {
+  let tags = if with_tags {
+    format!(", {TASK_TAGS}")
+  } else {
+    String::new()
+  };
+  sqlx::query_as::<_, Task>(&format!(
+    "SELECT {TASK_COLUMNS}{tags} FROM tasks WHERE {LISTED} ORDER BY {};",
+    sort.order_by()
+  ))
+  .bind(filter.done())
+  .bind(filter.archived())
+  .bind(list_id)
+  .fetch_all(&db.pool())
+  .await
+  .map_err(log_error("list_tasks"))
+}
+
+#[tauri::command]
+pub async fn list_tasks(
+  db: State<'_, AppDb>,
+  filter: TaskFilter,
+  sort: SortBy,
+  list_id: Option<i64>,
+  with_tags: Option<bool>,
+) -> Result<Vec<Task>, AppError> {
+  guard(
+    "list_tasks",
+    list(&db, filter, sort, list_id, with_tags.unwrap_or(false)),
+  )
+  .await
+}
+
+/// One page of `list_tasks`, plus how many tasks match the filter in total.
+/// `limit` is capped at `MAX_PAGE_SIZE`.
+#[tauri::command]
+pub async fn list_tasks_paged(
+  db: State<'_, AppDb>,
+  filter: TaskFilter,
+  sort: SortBy,
+  list_id: Option<i64>,
+  limit: u32,
+  offset: u32,
+  with_tags: Option<bool>,
+) -> Result<PagedTasks, AppError> {
+  guard("list_tasks_paged", async {
+    let tags = if with_tags.unwrap_or(false) {
+      format!(", {TASK_TAGS}")
+    } else {
+      String::new()
+    };
+
+    // one read transaction, so `total` and `items` come from the same snapshot
+    let mut tx = db
+      .pool()
+      .begin()
+      .await
+      .map_err(log_error("list_tasks_paged"))?;
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM tasks WHERE {LISTED};"))
+      .bind(filter.done())
+      .bind(filter.archived())
+      .bind(list_id)
+      .fetch_one(&mut *tx)
+      .await
+      .map_err(log_error("list_tasks_paged"))?;
+    let items = sqlx::query_as::<_, Task>(&format!(
+      "SELECT {TASK_COLUMNS}{tags} FROM tasks WHERE {LISTED} ORDER BY {} LIMIT ?4 OFFSET ?5;",
+      sort.order_by()
+    ))
+    .bind(filter.done())
+    .bind(filter.archived())
+    .bind(list_id)
+    .bind(limit.min(MAX_PAGE_SIZE))
+    .bind(offset)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(log_error("list_tasks_paged"))?;
+    tx.commit().await.map_err(log_error("list_tasks_paged"))?;
+
+    Ok(PagedTasks {
+      items,
+      total: total as u64,
+    })
+  })
+  .await
+}
+
+/// Bookkeeping for tasks a command just moved from open to done, inside its
+/// transaction: the completion counts towards the streak, and each recurring
+/// one spawns its next occurrence and hands the rule over to it, so
+/// un-completing and re-completing it doesn't spawn a duplicate. Returns the
+/// spawned tasks. Every way of completing a task goes through here.
+async fn completed(
+  conn: &mut SqliteConnection,
+  undo: &mut Recorder,
+  ids: &[i64],
+  at: DateTime<Utc>,
+) -> sqlx::Result<Vec<Task>> {
+  if ids.is_empty() {
+    return Ok(Vec::new());
+  }
+  streak::record(conn, at).await?;
+
+  let now = timestamp(&at);
+  let mut spawned = Vec::new();
+  for &id in ids {
+    let (due, repeat): (Option<DateTime<Utc>>, String) =
+      sqlx::query_as("SELECT due, repeat FROM tasks WHERE id = ?;")
+        .bind(id)
+        .fetch_one(&mut *conn)
+        .await?;
+    let Some(rule) = RepeatRule::from_column(&repeat) else {
+      continue;
+    };
+    let due = rule.next_after(due.unwrap_or(at));
+    let next = sqlx::query_as::<_, Task>(&format!(
+      "INSERT INTO tasks \
+         (title, notes, done, list_id, created_at, updated_at, due, repeat, priority, \
+          parent_id, sort_order) \
+       SELECT title, notes, 0, list_id, ?1, ?1, ?2, repeat, priority, \
+         parent_id, (SELECT MAX(sort_order) + 1 FROM tasks) \
+       FROM tasks WHERE id = ?3 \
+       RETURNING {TASK_COLUMNS};"
+    ))
+    .bind(&now)
+    .bind(timestamp(&due))
+    .bind(id)
+    .fetch_one(&mut *conn)
+    .await?;
+    // same updated_at as the completion, so the caller's copy stays current
+    sqlx::query("UPDATE tasks SET repeat = 'none', updated_at = ? WHERE id = ?;")
+      .bind(&now)
+      .bind(id)
+      .execute(&mut *conn)
+      .await?;
+    undo.created(next.id);
+    spawned.push(next);
+  }
+  Ok(spawned)
+}
+
+/// What `toggle_task_done` does, minus the change events.
+pub async fn toggle(
+  db: &AppDb,
+  history: &History,
+  id: i64,
+  cascade: bool,
+) -> Result<Toggled, AppError> {
+  let now = Utc::now();
+  let (task, next, completed_subtasks, entry) = with_retry("toggle_task_done", || async {
+    let mut tx = db.pool().begin().await?;
+    let mut tracked = vec![id];
+    if cascade {
+      tracked.extend(descendants(&mut tx, id).await?);
+    }
+    let mut undo = Recorder::start(&mut tx, "toggle_task_done", &tracked).await?;
+    let mut task = sqlx::query_as::<_, Task>(&format!(
+      "UPDATE tasks SET done = NOT done, completed_at = CASE WHEN done THEN NULL ELSE ?1 END, \
+         updated_at = ?1 \
+       WHERE id = ?2 \
+       RETURNING {TASK_COLUMNS};"
+    ))
+    .bind(timestamp(&now))
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(AppError::NotFound)?;
+
+    let mut next = None;
+    if task.done {
+      next = completed(&mut tx, &mut undo, &[id], now).await?.pop();
+      if next.is_some() {
+        task.repeat = None;
+      }
+    }
+
+    let mut completed_subtasks = Vec::new();
+    if task.done && cascade {
+      completed_subtasks = sqlx::query_scalar(&format!(
+        "{DESCENDANTS} \
+         UPDATE tasks SET done = 1, completed_at = ?2, updated_at = ?2 \
+         WHERE done = 0 AND id IN (SELECT id FROM descendants) \
+         RETURNING id;"
+      ))
+      .bind(id)
+      .bind(timestamp(&now))
+      .fetch_all(&mut *tx)
+      .await?;
+    }
+    let entry = undo.finish(&mut tx).await?;
+    tx.commit().await?;
+    Ok((task, next, completed_subtasks, entry))
+  })
+  .await?;
+  history.record(entry);
+  Ok(Toggled {
+    task,
+    next,
+    completed_subtasks,
+  })
+}
+
+/// Flips `done`. Completing a recurring task spawns its next occurrence in
+/// the same transaction and hands the rule over to it, so un-completing and
+/// re-completing the old one doesn't spawn a duplicate. With `cascade`,
+/// completing a task also completes all of its subtasks.
+#[tauri::command]
+pub async fn toggle_task_done(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  id: i64,
+  cascade: Option<bool>,
+) -> Result<Toggled, AppError> {
+  guard("toggle_task_done", async {
+    let toggled = toggle(&db, &history, id, cascade.unwrap_or(false)).await?;
+    events::task_changed(&app, id, ChangeKind::Updated);
+    if let Some(spawned) = &toggled.next {
+      events::task_changed(&app, spawned.id, ChangeKind::Created);
+    }
+    events::tasks_changed(
+      &app,
+      toggled.completed_subtasks.clone(),
+      ChangeKind::Updated,
+    );
+    Ok(toggled)
+  })
+  .await
+}
+
+/// What `delete_task` does, minus the change events. Returns the subtasks it
+/// changed, and whether they were deleted or moved up.
+pub async fn delete(
+  db: &AppDb,
+  history: &History,
+  id: i64,
+  hard: bool,
+  cascade: bool,
+) -> Result<(Vec<i64>, ChangeKind), AppError> {
+  let now = timestamp(&Utc::now());
+  let (subtasks, kind, entry) = with_retry("delete_task", || async {
+    let mut tx = db.pool().begin().await?;
+    let mut tracked = vec![id];
+    tracked.extend(if cascade {
+      descendants(&mut tx, id).await?
+    } else {
+      sqlx::query_scalar("SELECT id FROM tasks WHERE parent_id = ?;")
+        .bind(id)
+        .fetch_all(&mut *tx)
+        .await?
+    });
+    let undo = Recorder::start(&mut tx, "delete_task", &tracked).await?;
+
+    let (subtasks, kind) = if cascade {
+      let sql = if hard {
+        format!(
+          "{DESCENDANTS} DELETE FROM tasks WHERE id IN (SELECT id FROM descendants) RETURNING id;"
+        )
+      } else {
+        format!(
+          "{DESCENDANTS} \
+           UPDATE tasks SET deleted_at = COALESCE(deleted_at, ?2), updated_at = ?2 \
+           WHERE id IN (SELECT id FROM descendants) \
+           RETURNING id;"
+        )
+      };
+      let mut query = sqlx::query_scalar(&sql).bind(id);
+      if !hard {
+        query = query.bind(&now);
+      }
+      let ids: Vec<i64> = query.fetch_all(&mut *tx).await?;
+      (ids, ChangeKind::Deleted)
+    } else {
+      let ids: Vec<i64> = sqlx::query_scalar(
+        "UPDATE tasks SET parent_id = (SELECT parent_id FROM tasks WHERE id = ?1), updated_at = ?2 \
+         WHERE parent_id = ?1 \
+         RETURNING id;",
+      )
+      .bind(id)
+      .bind(&now)
+      .fetch_all(&mut *tx)
+      .await?;
+      (ids, ChangeKind::Updated)
+    };
+
+    let result = if hard {
+      sqlx::query("DELETE FROM tasks WHERE id = ?;")
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+    } else {
+      sqlx::query(
+        "UPDATE tasks SET deleted_at = COALESCE(deleted_at, ?1), updated_at = ?1 WHERE id = ?2;",
+      )
+      .bind(&now)
+      .bind(id)
+      .execute(&mut *tx)
+      .await
+    }?;
+    if result.rows_affected() == 0 {
+      return Err(AppError::NotFound.into());
+    }
+    let entry = undo.finish(&mut tx).await?;
+    tx.commit().await?;
+    Ok((subtasks, kind, entry))
+  })
+  .await?;
+  history.record(entry);
+  Ok((subtasks, kind))
+}
+
+/// Moves a task to the trash, or removes it for good when `hard` is set.
+/// Subtasks go with it when `cascade` is set; otherwise they move up to the
+/// deleted task's own parent.
+#[tauri::command]
+pub async fn delete_task(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  id: i64,
+  hard: bool,
+  cascade: Option<bool>,
+) -> Result<(), AppError> {
+  guard("delete_task", async {
+    let (subtasks, kind) = delete(&db, &history, id, hard, cascade.unwrap_or(false)).await?;
+    events::task_changed(&app, id, ChangeKind::Deleted);
+    events::tasks_changed(&app, subtasks, kind);
+    Ok(())
+  })
+  .await
+}
+
+#[tauri::command]
+pub async fn restore_task(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  id: i64,
+) -> Result<(), AppError> {
+  guard("restore_task", async {
+    let entry = with_retry("restore_task", || async {
+      let mut tx = db.pool().begin().await?;
+      let undo = Recorder::start(&mut tx, "restore_task", &[id]).await?;
+      let result = sqlx::query("UPDATE tasks SET deleted_at = NULL, updated_at = ? WHERE id = ?;")
+        .bind(timestamp(&Utc::now()))
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+      if result.rows_affected() == 0 {
+        return Err(AppError::NotFound.into());
+      }
+      let entry = undo.finish(&mut tx).await?;
+      tx.commit().await?;
+      Ok(entry)
+    })
+    .await?;
+    history.record(entry);
+
+    events::task_changed(&app, id, ChangeKind::Updated);
+    Ok(())
+  })
+  .await
+}
+
+async fn set_archived(
+  app: &AppHandle,
+  db: &AppDb,
+  history: &History,
+  id: i64,
+  archived: bool,
+) -> Result<(), AppError> {
+  let label = if archived {
+    "archive_task"
+  } else {
+    "unarchive_task"
+  };
+  let entry = with_retry(label, || async {
+    let mut tx = db.pool().begin().await?;
+    let undo = Recorder::start(&mut tx, label, &[id]).await?;
+    let result = sqlx::query(
+      "UPDATE tasks SET archived = ?, updated_at = ? WHERE id = ? AND deleted_at IS NULL;",
+    )
+    .bind(archived)
+    .bind(timestamp(&Utc::now()))
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+    if result.rows_affected() == 0 {
+      return Err(AppError::NotFound.into());
+    }
+    let entry = undo.finish(&mut tx).await?;
+    tx.commit().await?;
+    Ok(entry)
+  })
+  .await?;
+  history.record(entry);
+
+  events::task_changed(app, id, ChangeKind::Updated);
+  Ok(())
+}
+
+/// Hides a task from `list_tasks` except under the `archived` filter.
+#[tauri::command]
+pub async fn archive_task(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  id: i64,
+) -> Result<(), AppError> {
+  guard("archive_task", async {
+    set_archived(&app, &db, &history, id, true).await
+  })
+  .await
+}
+
+#[tauri::command]
+pub async fn unarchive_task(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  id: i64,
+) -> Result<(), AppError> {
+  guard("unarchive_task", async {
+    set_archived(&app, &db, &history, id, false).await
+  })
+  .await
+}
+
+/// Archives every task completed more than `days` days ago; returns how many.
+#[tauri::command]
+pub async fn bulk_archive_completed(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  days: u32,
+) -> Result<usize, AppError> {
+  guard("bulk_archive_completed", async {
+    let now = Utc::now();
+    let cutoff = now - Days::new(days.into());
+    let archived = with_retry("bulk_archive_completed", || async {
+      let mut tx = db.pool().begin().await?;
+      // tasks imported without a completion time fall back to their last change
+      let due: Vec<i64> = sqlx::query_scalar(
+        "SELECT id FROM tasks \
+         WHERE done = 1 AND archived = 0 AND deleted_at IS NULL \
+           AND COALESCE(completed_at, updated_at) < ?;",
+      )
+      .bind(timestamp(&cutoff))
+      .fetch_all(&mut *tx)
+      .await?;
+      if due.is_empty() {
+        return Ok(None);
+      }
+      let undo = Recorder::start(&mut tx, "bulk_archive_completed", &due).await?;
+      let sql = format!(
+        "UPDATE tasks SET archived = 1, updated_at = ? WHERE id IN ({}) RETURNING id;",
+        placeholders(due.len())
+      );
+      let mut query = sqlx::query_scalar::<_, i64>(&sql).bind(timestamp(&now));
+      for id in &due {
+        query = query.bind(id);
+      }
+      let archived = query.fetch_all(&mut *tx).await?;
+      let entry = undo.finish(&mut tx).await?;
+      tx.commit().await?;
+      Ok(Some((archived, entry)))
+    })
+    .await?;
+    let Some((archived, entry)) = archived else {
+      return Ok(0);
+    };
+    history.record(entry);
+
+    let count = archived.len();
+    events::tasks_changed(&app, archived, ChangeKind::Updated);
+    Ok(count)
+  })
+  .await
+}
+
+/// What `bulk_complete` does, minus the change events: returns the ids it
+/// completed and the next occurrences of the recurring ones among them.
+pub async fn complete_all(
+  db: &AppDb,
+  history: &History,
+  ids: &[i64],
+) -> Result<(Vec<i64>, Vec<Task>), AppError> {
+  if ids.is_empty() {
+    return Ok((Vec::new(), Vec::new()));
+  }
+
+  let sql = format!(
+    "UPDATE tasks SET done = 1, completed_at = ?, updated_at = ? \
+     WHERE done = 0 AND deleted_at IS NULL AND id IN ({}) \
+     RETURNING id;",
+    placeholders(ids.len())
+  );
+  let (changed, spawned, entry) = with_retry("bulk_complete", || async {
+    let mut tx = db.pool().begin().await?;
+    let mut undo = Recorder::start(&mut tx, "bulk_complete", ids).await?;
+    let now = Utc::now();
+    // sqlx counts unnumbered `?`s from the first argument, whatever `?N`s
+    // come before them, so every parameter here is unnumbered
+    let mut query = sqlx::query_scalar::<_, i64>(&sql)
+      .bind(timestamp(&now))
+      .bind(timestamp(&now));
+    for id in ids {
+      query = query.bind(id);
+    }
+    let changed = query.fetch_all(&mut *tx).await?;
+    let spawned = completed(&mut tx, &mut undo, &changed, now).await?;
+    let entry = undo.finish(&mut tx).await?;
+    tx.commit().await?;
+    Ok((changed, spawned, entry))
+  })
+  .await?;
+  history.record(entry);
+  Ok((changed, spawned))
+}
+
+/// Marks every given task done; returns how many were actually still open.
+/// Recurring ones spawn their next occurrence, as with `toggle_task_done`.
+#[tauri::command]
+pub async fn bulk_complete(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  ids: Vec<i64>,
+) -> Result<usize, AppError> {
+  guard("bulk_complete", async {
+    let (changed, spawned) = complete_all(&db, &history, &ids).await?;
+    let count = changed.len();
+    events::tasks_changed(&app, changed, ChangeKind::Updated);
+    events::tasks_changed(
+      &app,
+      spawned.into_iter().map(|t| t.id).collect(),
+      ChangeKind::Created,
+    );
+    Ok(count)
+  })
+  .await
+}
+
+/// Full-text search over title and notes, best matches first.
+#[tauri::command]
+pub async fn search_tasks(db: State<'_, AppDb>, query: String) -> Result<Vec<Task>, AppError> {
+  guard("search_tasks", async {
+    let Some(fts) = fts_query(&query) else {
+      return Ok(Vec::new());
+    };
+
+    sqlx::query_as::<_, Task>(&format!(
+      "SELECT {TASK_COLUMNS} FROM tasks \
+       JOIN (SELECT rowid AS task_id, rank FROM tasks_fts WHERE tasks_fts MATCH ?) AS hits \
+         ON hits.task_id = tasks.id \
+       WHERE deleted_at IS NULL \
+       ORDER BY hits.rank;"
+    ))
+    .bind(fts)
+    .fetch_all(&db.pool())
+    .await
+    .map_err(log_error("search_tasks"))
+  })
+  .await
+}
+
+#[tauri::command]
+pub async fn set_priority(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  id: i64,
+  priority: Priority,
+) -> Result<Task, AppError> {
+  guard("set_priority", async {
+    let (task, entry) = with_retry("set_priority", || async {
+      let mut tx = db.pool().begin().await?;
+      let undo = Recorder::start(&mut tx, "set_priority", &[id]).await?;
+      let task = sqlx::query_as::<_, Task>(&format!(
+        "UPDATE tasks SET priority = ?, updated_at = ? WHERE id = ? RETURNING {TASK_COLUMNS};"
+      ))
+      .bind(priority.to_column())
+      .bind(timestamp(&Utc::now()))
+      .bind(id)
+      .fetch_optional(&mut *tx)
+      .await?
+      .ok_or(AppError::NotFound)?;
+      let entry = undo.finish(&mut tx).await?;
+      tx.commit().await?;
+      Ok((task, entry))
+    })
+    .await?;
+    history.record(entry);
+
+    events::task_changed(&app, id, ChangeKind::Updated);
+    Ok(task)
+  })
+  .await
+}
+
+/// What `update_task` does, minus the change events; takes the pool directly,
+/// so it runs without an app (integration tests). Also returns the next
+/// occurrence, if the update completed a recurring task.
+pub async fn update(
+  db: &AppDb,
+  history: &History,
+  notes: &Notes,
+  id: i64,
+  patch: TaskPatch,
+  expected_updated_at: Option<String>,
+) -> Result<(Task, Option<Task>), AppError> {
+  let title = match &patch.title {
+    Some(title) if title.trim().is_empty() => {
+      return Err(AppError::validation("title must not be empty"))
+    }
+    Some(title) => Some(title.trim().to_string()),
+    None => None,
+  };
+  let due = match &patch.due {
+    Some(Some(due)) => Some(Some(parse_timestamp("due", due)?)),
+    Some(None) => Some(None),
+    None => None,
+  };
+  let expected = expected_updated_at
+    .map(|e| parse_timestamp("expected_updated_at", &e))
+    .transpose()?;
+  if patch.notes.is_some() {
+    // a value still waiting in `save_notes` would land on top of this one
+    notes.discard(id).await;
+  }
+
+  let (task, next, entry) = with_retry("update_task", || async {
+    let at = Utc::now();
+    let now = timestamp(&at);
+    let mut query = QueryBuilder::<Sqlite>::new("UPDATE tasks SET updated_at = ");
+    query.push_bind(now.clone());
+    if let Some(title) = &title {
+      query.push(", title = ").push_bind(title.clone());
+    }
+    if let Some(notes) = &patch.notes {
+      query.push(", notes = ").push_bind(notes.clone());
+    }
+    if let Some(done) = patch.done {
+      query.push(", done = ").push_bind(done);
+      query
+        .push(", completed_at = CASE WHEN ")
+        .push_bind(done)
+        .push(" THEN COALESCE(completed_at, ")
+        .push_bind(now)
+        .push(") END");
+    }
+    if let Some(due) = &due {
+      query
+        .push(", due = ")
+        .push_bind(due.as_ref().map(timestamp));
+    }
+    if let Some(repeat) = patch.repeat {
+      query
+        .push(", repeat = ")
+        .push_bind(RepeatRule::to_column(repeat));
+    }
+    if let Some(priority) = patch.priority {
+      query.push(", priority = ").push_bind(priority.to_column());
+    }
+    if let Some(parent_id) = patch.parent_id {
+      query.push(", parent_id = ").push_bind(parent_id);
+    }
+    query.push(" WHERE id = ").push_bind(id);
+    if let Some(expected) = &expected {
+      query
+        .push(" AND updated_at = ")
+        .push_bind(timestamp(expected));
+    }
+    query.push(format!(" RETURNING {TASK_COLUMNS};"));
+
+    let mut tx = db.pool().begin().await?;
+    if let Some(Some(parent_id)) = patch.parent_id {
+      check_parent(&mut tx, Some(id), parent_id).await?;
+    }
+    let mut undo = Recorder::start(&mut tx, "update_task", &[id]).await?;
+    let was_done: Option<bool> = sqlx::query_scalar("SELECT done FROM tasks WHERE id = ?;")
+      .bind(id)
+      .fetch_optional(&mut *tx)
+      .await?;
+    let updated = query
+      .build_query_as::<Task>()
+      .fetch_optional(&mut *tx)
+      .await?;
+    let Some(mut task) = updated else {
+      let exists: Option<i64> = sqlx::query_scalar("SELECT id FROM tasks WHERE id = ?;")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+      return Err(
+        if exists.is_some() {
+          AppError::Conflict
+        } else {
+          AppError::NotFound
+        }
+        .into(),
+      );
+    };
+    let mut next = None;
+    if was_done == Some(false) && task.done {
+      next = completed(&mut tx, &mut undo, &[id], at).await?.pop();
+      if next.is_some() {
+        task.repeat = None;
+      }
+    }
+    let entry = undo.finish(&mut tx).await?;
+    tx.commit().await?;
+    Ok((task, next, entry))
+  })
+  .await?;
+  history.record(entry);
+  Ok((task, next))
+}
+
+/// Writes only the fields present in `patch`. With `expected_updated_at`, the
+/// write goes through only if nobody else changed the task since; otherwise it
+/// fails with `conflict` and the caller should reload. New notes replace any
+/// `save_notes` hasn't written yet. Completing a recurring task spawns its next
+/// occurrence, as with `toggle_task_done`.
+#[tauri::command]
+pub async fn update_task(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  notes: State<'_, Notes>,
+  id: i64,
+  patch: TaskPatch,
+  expected_updated_at: Option<String>,
+) -> Result<Task, AppError> {
+  guard("update_task", async {
+    let (task, next) = update(&db, &history, &notes, id, patch, expected_updated_at).await?;
+    events::task_changed(&app, id, ChangeKind::Updated);
+    if let Some(spawned) = next {
+      events::task_changed(&app, spawned.id, ChangeKind::Created);
+    }
+    Ok(task)
+  })
+  .await
+}
+
+/// One task with its tags, and its checklist with `include_checklist`, or
+/// `None` if there's no such id. Tasks in the trash are still returned, so
+/// links to them keep working.
+#[tauri::command]
+pub async fn get_task(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  notes: State<'_, Notes>,
+  id: i64,
+  include_checklist: Option<bool>,
+) -> Result<Option<Task>, AppError> {
+  guard("get_task", async {
+    match notes.flush(&app, id).await {
+      // nothing to read either way
+      Ok(()) | Err(AppError::NotFound) => {}
+      Err(e) => return Err(e),
+    }
+    let mut conn = db.pool().acquire().await.map_err(log_error("get_task"))?;
+    let task = sqlx::query_as::<_, Task>(&format!(
+      "SELECT {TASK_COLUMNS}, {TASK_TAGS} FROM tasks WHERE id = ?;"
+    ))
+    .bind(id)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(log_error("get_task"))?;
+    let Some(mut task) = task else {
+      return Ok(None);
+    };
+    if include_checklist.unwrap_or(false) {
+      task.checklist = Some(
+        checklist::items(&mut conn, id)
+          .await
+          .map_err(log_error("get_task"))?,
+      );
+    }
+    Ok(Some(task))
+  })
+  .await
+}
+
+/// Direct children of `parent_id`, oldest first.
+#[tauri::command]
+pub async fn list_subtasks(db: State<'_, AppDb>, parent_id: i64) -> Result<Vec<Task>, AppError> {
+  guard("list_subtasks", async {
+    sqlx::query_as::<_, Task>(&format!(
+      "SELECT {TASK_COLUMNS} FROM tasks \
+       WHERE parent_id = ? AND deleted_at IS NULL \
+       ORDER BY created_at ASC, id ASC;"
+    ))
+    .bind(parent_id)
+    .fetch_all(&db.pool())
+    .await
+    .map_err(log_error("list_subtasks"))
+  })
+  .await
+}
+
+/// Below this, halving the gap between two neighbours would soon run out of
+/// f64 precision, so `reorder_task` renumbers everything first.
+const MIN_ORDER_GAP: f64 = 1e-9;
+
+/// A `sort_order` between the task at `after_id` (or the top of the list) and
+/// the one currently after it, leaving `id` itself out. `None` when that gap
+/// is too narrow to split.
+async fn order_after(
+  conn: &mut SqliteConnection,
+  id: i64,
+  after_id: Option<i64>,
+) -> Result<Option<f64>, TxError> {
+  let lo: Option<f64> = match after_id {
+    Some(after_id) => Some(
+      sqlx::query_scalar("SELECT sort_order FROM tasks WHERE id = ? AND deleted_at IS NULL;")
+        .bind(after_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(AppError::NotFound)?,
+    ),
+    None => None,
+  };
+  let hi: Option<f64> = sqlx::query_scalar(
+    "SELECT MIN(sort_order) FROM tasks \
+     WHERE deleted_at IS NULL AND id != ?1 AND (?2 IS NULL OR sort_order > ?2);",
+  )
+  .bind(id)
+  .bind(lo)
+  .fetch_one(&mut *conn)
+  .await?;
+
+  Ok(match (lo, hi) {
+    (Some(lo), Some(hi)) => (hi - lo >= MIN_ORDER_GAP).then(|| lo + (hi - lo) / 2.0),
+    (Some(lo), None) => Some(lo + 1.0),
+    (None, Some(hi)) => Some(hi - 1.0),
+    (None, None) => Some(1.0),
+  })
+}
+
+/// Moves a task to just after `after_id`, or to the top when it's `None`, for
+/// the `manual_order` sort. Normally only this task's row changes.
+#[tauri::command]
+pub async fn reorder_task(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  id: i64,
+  after_id: Option<i64>,
+) -> Result<Task, AppError> {
+  guard("reorder_task", async {
+    if after_id == Some(id) {
+      return Err(AppError::validation("a task cannot be placed after itself"));
+    }
+
+    let now = timestamp(&Utc::now());
+    let (task, mut renumbered, entry) = with_retry("reorder_task", || async {
+      let mut tx = db.pool().begin().await?;
+      let mut undo = Recorder::start(&mut tx, "reorder_task", &[id]).await?;
+      let mut renumbered = Vec::new();
+      let order = match order_after(&mut tx, id, after_id).await? {
+        Some(order) => order,
+        None => {
+          // spread everything back out to whole numbers, keeping the current order
+          let all: Vec<i64> = sqlx::query_scalar("SELECT id FROM tasks;")
+            .fetch_all(&mut *tx)
+            .await?;
+          undo.track(&mut tx, &all).await?;
+          renumbered = sqlx::query_scalar::<_, i64>(
+            "UPDATE tasks SET sort_order = r.pos, updated_at = ?1 \
+             FROM (SELECT id AS task_id, ROW_NUMBER() OVER (ORDER BY sort_order, id) AS pos \
+                   FROM tasks) AS r \
+             WHERE tasks.id = r.task_id AND tasks.sort_order IS NOT r.pos \
+             RETURNING id;",
+          )
+          .bind(&now)
+          .fetch_all(&mut *tx)
+          .await?;
+          order_after(&mut tx, id, after_id)
+            .await?
+            .ok_or_else(|| AppError::Db("could not make room to reorder".into()))?
+        }
+      };
+
+      let task = sqlx::query_as::<_, Task>(&format!(
+        "UPDATE tasks SET sort_order = ?, updated_at = ? \
+         WHERE id = ? AND deleted_at IS NULL \
+         RETURNING {TASK_COLUMNS};"
+      ))
+      .bind(order)
+      .bind(&now)
+      .bind(id)
+      .fetch_optional(&mut *tx)
+      .await?
+      .ok_or(AppError::NotFound)?;
+      let entry = undo.finish(&mut tx).await?;
+      tx.commit().await?;
+      Ok((task, renumbered, entry))
+    })
+    .await?;
+    history.record(entry);
+
+    renumbered.retain(|&other| other != id);
+    events::tasks_changed(&app, renumbered, ChangeKind::Updated);
+    events::task_changed(&app, id, ChangeKind::Updated);
+    Ok(task)
+  })
+  .await
+}
+
+/// Copies a task (title with a " (copy)" suffix, notes, priority, due, tags)
+/// into a new open task in the same place. The copy shares nothing with the
+/// original afterwards.
+#[tauri::command]
+pub async fn duplicate_task(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  id: i64,
+) -> Result<Task, AppError> {
+  guard("duplicate_task", async {
+    let now = timestamp(&Utc::now());
+    let (task, entry) = with_retry("duplicate_task", || async {
+      let mut tx = db.pool().begin().await?;
+      let copy: i64 = sqlx::query_scalar(
+        "INSERT INTO tasks \
+           (title, notes, done, list_id, created_at, updated_at, due, priority, parent_id, \
+            sort_order) \
+         SELECT title || ' (copy)', notes, 0, list_id, ?1, ?1, due, priority, parent_id, \
+           (SELECT MAX(sort_order) + 1 FROM tasks) \
+         FROM tasks WHERE id = ?2 AND deleted_at IS NULL \
+         RETURNING id;",
+      )
+      .bind(&now)
+      .bind(id)
+      .fetch_optional(&mut *tx)
+      .await?
+      .ok_or(AppError::NotFound)?;
+
+      sqlx::query(
+        "INSERT INTO task_tags (task_id, tag_id) SELECT ?, tag_id FROM task_tags WHERE task_id = ?;",
+      )
+      .bind(copy)
+      .bind(id)
+      .execute(&mut *tx)
+      .await?;
+      let task = sqlx::query_as::<_, Task>(&format!(
+        "SELECT {TASK_COLUMNS}, {TASK_TAGS} FROM tasks WHERE id = ?;"
+      ))
+      .bind(copy)
+      .fetch_one(&mut *tx)
+      .await?;
+      let mut undo = Recorder::start(&mut tx, "duplicate_task", &[]).await?;
+      undo.created(copy);
+      let entry = undo.finish(&mut tx).await?;
+      tx.commit().await?;
+      Ok((task, entry))
+    })
+    .await?;
+    history.record(entry);
+
+    events::task_changed(&app, task.id, ChangeKind::Created);
+    Ok(task)
+  })
+  .await
+}
+
+/// What `merge_tasks` does, minus the pending notes and the change events:
+/// returns the kept task, the ids that went to the trash and the subtasks that
+/// moved over. Takes the pool directly, so it runs without an app
+/// (integration tests).
+pub async fn merge(
+  db: &AppDb,
+  history: &History,
+  keep_id: i64,
+  merge_ids: Vec<i64>,
+) -> Result<(Task, Vec<i64>, Vec<i64>), AppError> {
+  let mut merged: Vec<i64> = Vec::new();
+  for id in merge_ids {
+    if id != keep_id && !merged.contains(&id) {
+      merged.push(id);
+    }
+  }
+
+  if merged.is_empty() {
+    let task = sqlx::query_as::<_, Task>(&format!(
+      "SELECT {TASK_COLUMNS}, {TASK_TAGS} FROM tasks WHERE id = ? AND deleted_at IS NULL;"
+    ))
+    .bind(keep_id)
+    .fetch_optional(&db.pool())
+    .await
+    .map_err(log_error("merge_tasks"))?
+    .ok_or(AppError::NotFound)?;
+    return Ok((task, merged, Vec::new()));
+  }
+  let mut all = vec![keep_id];
+  all.extend(&merged);
+  let listed = placeholders(all.len());
+
+  let now = timestamp(&Utc::now());
+  let (task, children, entry) = with_retry("merge_tasks", || async {
+    let mut tx = db.pool().begin().await?;
+    let sql = format!("SELECT COUNT(*) FROM tasks WHERE deleted_at IS NULL AND id IN ({listed});");
+    let mut query = sqlx::query_scalar::<_, i64>(&sql);
+    for id in &all {
+      query = query.bind(id);
+    }
+    if query.fetch_one(&mut *tx).await? as usize != all.len() {
+      return Err(AppError::NotFound.into());
+    }
+    for &id in &merged {
+      // its subtasks move over, so the kept task can't be one of them
+      check_parent(&mut tx, Some(id), keep_id).await?;
+    }
+
+    let mut undo = Recorder::start(&mut tx, "merge_tasks", &all).await?;
+    let sql = format!(
+      "SELECT id FROM tasks WHERE parent_id IN ({});",
+      placeholders(merged.len())
+    );
+    let mut query = sqlx::query_scalar::<_, i64>(&sql);
+    for id in &merged {
+      query = query.bind(id);
+    }
+    let children = query.fetch_all(&mut *tx).await?;
+    undo.track(&mut tx, &children).await?;
+
+    // the longest notes win; the kept task's on a tie
+    let sql = format!(
+      "SELECT notes FROM tasks \
+       WHERE id IN ({listed}) AND TRIM(COALESCE(notes, '')) <> '' \
+       ORDER BY LENGTH(notes) DESC, id = ? DESC LIMIT 1;"
+    );
+    let mut query = sqlx::query_scalar::<_, Option<String>>(&sql);
+    for id in &all {
+      query = query.bind(id);
+    }
+    let notes = query
+      .bind(keep_id)
+      .fetch_optional(&mut *tx)
+      .await?
+      .flatten();
+    let sql = format!(
+      "UPDATE tasks SET notes = ?, updated_at = ?, \
+         created_at = (SELECT MIN(created_at) FROM tasks WHERE id IN ({listed})) \
+       WHERE id = ?;"
+    );
+    let mut query = sqlx::query(&sql).bind(notes).bind(&now);
+    for id in &all {
+      query = query.bind(id);
+    }
+    query.bind(keep_id).execute(&mut *tx).await?;
+
+    for &id in &merged {
+      sqlx::query(
+        "INSERT OR IGNORE INTO task_tags (task_id, tag_id) \
+         SELECT ?, tag_id FROM task_tags WHERE task_id = ?;",
+      )
+      .bind(keep_id)
+      .bind(id)
+      .execute(&mut *tx)
+      .await?;
+      let offset: i64 = sqlx::query_scalar(
+        "SELECT COALESCE(MAX(position) + 1, 0) FROM checklist_items WHERE task_id = ?;",
+      )
+      .bind(keep_id)
+      .fetch_one(&mut *tx)
+      .await?;
+      sqlx::query(
+        "UPDATE checklist_items SET task_id = ?, position = position + ? WHERE task_id = ?;",
+      )
+      .bind(keep_id)
+      .bind(offset)
+      .bind(id)
+      .execute(&mut *tx)
+      .await?;
+      sqlx::query("UPDATE attachments SET task_id = ? WHERE task_id = ?;")
+        .bind(keep_id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+      sqlx::query("UPDATE tasks SET parent_id = ?, updated_at = ? WHERE parent_id = ?;")
+        .bind(keep_id)
+        .bind(&now)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+      sqlx::query("UPDATE tasks SET deleted_at = ?1, updated_at = ?1 WHERE id = ?2;")
+        .bind(&now)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let task = sqlx::query_as::<_, Task>(&format!(
+      "SELECT {TASK_COLUMNS}, {TASK_TAGS} FROM tasks WHERE id = ?;"
+    ))
+    .bind(keep_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let entry = undo.finish(&mut tx).await?;
+    tx.commit().await?;
+    Ok((task, children, entry))
+  })
+  .await?;
+  history.record(entry);
+  Ok((task, merged, children))
+}
+
+/// Folds duplicates into `keep_id`: it gains their tags, checklist items
+/// (after its own) and attachments, their subtasks, the earliest
+/// `created_at` among them and the longest notes (notes still waiting in
+/// `save_notes` included), and the merged tasks go to the trash. Ids equal to
+/// `keep_id` are skipped. Undo brings the merged tasks back with their
+/// checklist items and attachments.
+#[tauri::command]
+pub async fn merge_tasks(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  notes: State<'_, Notes>,
+  keep_id: i64,
+  merge_ids: Vec<i64>,
+) -> Result<Task, AppError> {
+  guard("merge_tasks", async {
+    // unsaved notes take part in picking the longest, and the kept task's are
+    // rewritten
+    for &id in [keep_id].iter().chain(&merge_ids) {
+      match notes.flush(&app, id).await {
+        // `merge` reports it
+        Ok(()) | Err(AppError::NotFound) => {}
+        Err(e) => return Err(e),
+      }
+    }
+    let (task, merged, children) = merge(&db, &history, keep_id, merge_ids).await?;
+    events::task_changed(&app, keep_id, ChangeKind::Updated);
+    events::tasks_changed(&app, merged, ChangeKind::Deleted);
+    events::tasks_changed(&app, children, ChangeKind::Updated);
+    Ok(task)
+  })
+  .await
+}
+
+/// Pushes a task's due date back by `duration`, counted from now. With
+/// `from_due`, a due date that's still in the future is pushed back instead.
+#[tauri::command]
+pub async fn snooze_task(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  id: i64,
+  duration: SnoozeSpec,
+  from_due: Option<bool>,
+) -> Result<Task, AppError> {
+  guard("snooze_task", async {
+    let now = Utc::now();
+    let (task, entry) = with_retry("snooze_task", || async {
+      let mut tx = db.pool().begin().await?;
+      let due: Option<DateTime<Utc>> =
+        sqlx::query_scalar("SELECT due FROM tasks WHERE id = ? AND deleted_at IS NULL;")
+          .bind(id)
+          .fetch_optional(&mut *tx)
+          .await?
+          .ok_or(AppError::NotFound)?;
+      let base = match due {
+        Some(due) if from_due.unwrap_or(false) && due > now => due,
+        _ => now,
+      };
+
+      let undo = Recorder::start(&mut tx, "snooze_task", &[id]).await?;
+      let task = sqlx::query_as::<_, Task>(&format!(
+        "UPDATE tasks SET due = ?, updated_at = ? WHERE id = ? RETURNING {TASK_COLUMNS};"
+      ))
+      .bind(timestamp(&duration.after(base)))
+      .bind(timestamp(&now))
+      .bind(id)
+      .fetch_one(&mut *tx)
+      .await?;
+      let entry = undo.finish(&mut tx).await?;
+      tx.commit().await?;
+      Ok((task, entry))
+    })
+    .await?;
+    history.record(entry);
+
+    events::task_changed(&app, id, ChangeKind::Updated);
+    Ok(task)
+  })
+  .await
+}
+
+/// Rolls every open task due before today (local) forward to today, keeping
+/// its time of day; returns how many moved. Recurring tasks are left alone,
+/// since completing them is what schedules the next one.
+#[tauri::command]
+pub async fn move_overdue_to_today(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+) -> Result<usize, AppError> {
+  guard("move_overdue_to_today", async {
+    let now = Utc::now();
+    let today = now.with_timezone(&Local).date_naive();
+    let (moved, entry) = with_retry("move_overdue_to_today", || async {
+      let mut tx = db.pool().begin().await?;
+      let overdue: Vec<(i64, DateTime<Utc>, String)> = sqlx::query_as(
+        "SELECT id, due, repeat FROM tasks \
+         WHERE done = 0 AND deleted_at IS NULL AND archived = 0 AND due < ?;",
+      )
+      .bind(timestamp(&local_midnight(today)))
+      .fetch_all(&mut *tx)
+      .await?;
+      let overdue: Vec<(i64, DateTime<Utc>)> = overdue
+        .into_iter()
+        .filter(|(_, _, repeat)| RepeatRule::from_column(repeat).is_none())
+        .map(|(id, due, _)| (id, due))
+        .collect();
+
+      let ids: Vec<i64> = overdue.iter().map(|(id, _)| *id).collect();
+      let undo = Recorder::start(&mut tx, "move_overdue_to_today", &ids).await?;
+      let mut moved = Vec::new();
+      for (id, due) in &overdue {
+        let Some(to) = due::local(today.and_time(due.with_timezone(&Local).time())) else {
+          continue;
+        };
+        sqlx::query("UPDATE tasks SET due = ?, updated_at = ? WHERE id = ?;")
+          .bind(timestamp(&to))
+          .bind(timestamp(&now))
+          .bind(id)
+          .execute(&mut *tx)
+          .await?;
+        moved.push(*id);
+      }
+      let entry = undo.finish(&mut tx).await?;
+      tx.commit().await?;
+      Ok((moved, entry))
+    })
+    .await?;
+    history.record(entry);
+
+    let count = moved.len();
+    events::tasks_changed(&app, moved, ChangeKind::Updated);
+    Ok(count)
+  })
+  .await
+}
+
+/// Typed-out confirmation `reset_all` insists on.
+const RESET_CONFIRMATION: &str = "RESET";
+
+/// Deletes every task and tag and restarts their ids from 1, all or nothing.
+/// Only goes ahead when `confirm` is exactly `"RESET"`. Undo history, unsaved
+/// notes and sent reminders go too: they'd otherwise apply to the new tasks
+/// that get the old ids.
+#[tauri::command]
+pub async fn reset_all(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  notes: State<'_, Notes>,
+  notified: State<'_, Notified>,
+  confirm: String,
+) -> Result<(), AppError> {
+  guard("reset_all", async {
+    if confirm != RESET_CONFIRMATION {
+      return Err(AppError::Validation(format!(
+        "pass confirm = {RESET_CONFIRMATION:?} to delete everything"
+      )));
+    }
+
+    let deleted = with_retry("reset_all", || async {
+      let mut tx = db.pool().begin().await?;
+      sqlx::query("DELETE FROM task_tags;")
+        .execute(&mut *tx)
+        .await?;
+      let deleted: Vec<i64> = sqlx::query_scalar("DELETE FROM tasks RETURNING id;")
+        .fetch_all(&mut *tx)
+        .await?;
+      sqlx::query("DELETE FROM tags;").execute(&mut *tx).await?;
+      sqlx::query("DELETE FROM sqlite_sequence WHERE name IN ('tasks', 'tags');")
+        .execute(&mut *tx)
+        .await?;
+      tx.commit().await?;
+      Ok(deleted)
+    })
+    .await?;
+    history.clear();
+    notes.discard_all().await;
+    notified.0.lock().unwrap().clear();
+
+    log::warn!("reset_all deleted {} tasks", deleted.len());
+    events::tasks_changed(&app, deleted, ChangeKind::Deleted);
+    Ok(())
+  })
+  .await
+}
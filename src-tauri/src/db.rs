@@ -0,0 +1,325 @@
+use chrono::Utc;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::{ConnectOptions, Pool, Sqlite};
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_sql::{DbInstances, DbPool};
+
+use crate::backup;
+use crate::error::AppError;
+use crate::history::History;
+use crate::logging::log_error;
+use crate::migrations;
+use crate::notes::Notes;
+use crate::reminders::{self, Notified};
+
+/// Upper bound on pooled connections; SQLite serializes writers anyway, this
+/// just lets a few reads overlap.
+const MAX_CONNECTIONS: u32 = 5;
+
+/// How long (ms) a connection waits on another writer's lock before failing
+/// with "database is locked".
+pub const BUSY_TIMEOUT_MS: u32 = 5000;
+
+/// SQLite primary result codes for another connection holding a lock.
+const SQLITE_BUSY: i32 = 5;
+const SQLITE_LOCKED: i32 = 6;
+
+/// SQLite primary result codes for a damaged file.
+const SQLITE_CORRUPT: i32 = 11;
+const SQLITE_NOTADB: i32 = 26;
+
+/// How often `with_retry` tries again, and how long it waits the first time;
+/// the wait doubles after each attempt.
+const MAX_RETRIES: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// The primary result code of a database error (sqlx reports extended ones).
+fn primary_code(e: &sqlx::Error) -> Option<i32> {
+  let code = match e {
+    sqlx::Error::Database(e) => e.code().and_then(|c| c.parse::<i32>().ok()),
+    _ => None,
+  };
+  code.map(|c| c & 0xff)
+}
+
+pub(crate) fn is_corruption(e: &sqlx::Error) -> bool {
+  matches!(primary_code(e), Some(SQLITE_CORRUPT | SQLITE_NOTADB))
+}
+
+pub(crate) fn is_busy(e: &sqlx::Error) -> bool {
+  matches!(primary_code(e), Some(SQLITE_BUSY | SQLITE_LOCKED))
+}
+
+/// Why a transaction passed to `with_retry` failed. Database errors are kept
+/// as they are until `with_retry` has seen whether they're worth a retry.
+#[derive(Debug)]
+pub enum TxError {
+  Db(sqlx::Error),
+  /// The command's own verdict (not found, invalid input), passed on as is.
+  App(AppError),
+}
+
+impl From<sqlx::Error> for TxError {
+  fn from(e: sqlx::Error) -> Self {
+    Self::Db(e)
+  }
+}
+
+impl From<AppError> for TxError {
+  fn from(e: AppError) -> Self {
+    Self::App(e)
+  }
+}
+
+/// Runs the transaction `attempt` builds, starting it over up to
+/// `MAX_RETRIES` times while SQLite reports the database busy or locked. The
+/// busy timeout already covers most contention, but a transaction that read
+/// before writing fails at once when another connection committed in between,
+/// and only a fresh start helps there. Other errors come back on the first
+/// failure, logged under `op` if they came from the database.
+pub async fn with_retry<T, F, Fut>(op: &'static str, mut attempt: F) -> Result<T, AppError>
+where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = Result<T, TxError>>,
+{
+  let mut delay = RETRY_DELAY;
+  let mut retries = 0;
+  loop {
+    match attempt().await {
+      Ok(value) => return Ok(value),
+      Err(TxError::Db(e)) if is_busy(&e) && retries < MAX_RETRIES => {
+        retries += 1;
+        log::warn!("{op}: {e}, retrying in {}ms", delay.as_millis());
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+      }
+      Err(TxError::Db(e)) => return Err(log_error(op)(e)),
+      Err(TxError::App(e)) => return Err(e),
+    }
+  }
+}
+
+/// Runs `PRAGMA integrity_check` on the file at `path`, if there is one.
+/// Errors that mean the file is damaged count as a failed check; anything
+/// else (e.g. it's locked) is returned as is.
+pub async fn is_intact(path: &Path) -> sqlx::Result<bool> {
+  if !path.exists() {
+    return Ok(true);
+  }
+  let check = async {
+    let mut conn = SqliteConnectOptions::new().filename(path).connect().await?;
+    let rows: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check;")
+      .fetch_all(&mut conn)
+      .await?;
+    Ok::<_, sqlx::Error>(rows == ["ok"])
+  };
+  match check.await {
+    Err(e) if is_corruption(&e) => Ok(false),
+    result => result,
+  }
+}
+
+/// Moves a damaged database (and its `-wal`/`-shm` files) aside to
+/// `tasks.corrupt.<timestamp>.db` next to it, returning the new path.
+pub fn quarantine(path: &Path) -> io::Result<PathBuf> {
+  let stamp = Utc::now().format("%Y%m%dT%H%M%SZ");
+  let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+  let moved = path.with_file_name(format!("{stem}.corrupt.{stamp}.db"));
+  fs::rename(path, &moved)?;
+
+  for suffix in ["-wal", "-shm"] {
+    let mut side = path.as_os_str().to_owned();
+    side.push(suffix);
+    let side = PathBuf::from(side);
+    if side.exists() {
+      let mut to = moved.as_os_str().to_owned();
+      to.push(suffix);
+      if let Err(e) = fs::rename(&side, PathBuf::from(to)) {
+        log::warn!("could not move {} aside: {e}", side.display());
+      }
+    }
+  }
+  Ok(moved)
+}
+
+/// `value` as an SQL string literal, for pragmas that can't take a bound
+/// parameter.
+pub(crate) fn quote(value: &str) -> String {
+  format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Connection pool shared by every command, created once in `setup` (or by
+/// `set_db_key`, for an encrypted database). `reopen` swaps in a new one, so
+/// take it with `pool` where it's used rather than holding on to it.
+pub struct AppDb(RwLock<Pool<Sqlite>>);
+
+impl AppDb {
+  pub fn new(pool: Pool<Sqlite>) -> Self {
+    Self(RwLock::new(pool))
+  }
+
+  /// The current pool (a cheap handle, not a copy of its connections).
+  pub fn pool(&self) -> Pool<Sqlite> {
+    self.0.read().unwrap().clone()
+  }
+
+  /// Applies pending migrations on a dedicated connection and only then opens
+  /// the pool, so no pooled connection can observe a half-migrated schema.
+  /// `key` is the SQLCipher passphrase of an encrypted database.
+  pub async fn open(path: &Path, key: Option<&str>) -> sqlx::Result<Self> {
+    let mut options = SqliteConnectOptions::new()
+      .filename(path)
+      .create_if_missing(true)
+      .foreign_keys(true);
+    if let Some(key) = key {
+      // sqlx sends `key` before every other pragma, as SQLCipher requires
+      options = options.pragma("key", quote(key));
+    }
+    Self::connect(options).await.map(Self::new)
+  }
+
+  async fn connect(options: SqliteConnectOptions) -> sqlx::Result<Pool<Sqlite>> {
+    migrations::run(&options).await?;
+
+    // Every window's frontend talks to this file through the SQL plugin's pool
+    // (see `share`), alongside ours. In the default rollback-journal mode a
+    // writer blocks all readers and vice versa, so one window's save could fail
+    // another's query outright; WAL lets readers carry on during a write, and
+    // the busy timeout makes competing writers queue instead of erroring.
+    // journal_mode sticks to the file, so the plugin's connections get WAL too;
+    // sqlx gives every connection a five-second busy timeout by default.
+    SqlitePoolOptions::new()
+      .max_connections(MAX_CONNECTIONS)
+      .after_connect(|conn, _meta| {
+        Box::pin(async move {
+          sqlx::query("PRAGMA journal_mode = WAL;")
+            .execute(&mut *conn)
+            .await?;
+          sqlx::query(&format!("PRAGMA busy_timeout = {BUSY_TIMEOUT_MS};"))
+            .execute(&mut *conn)
+            .await?;
+          Ok(())
+        })
+      })
+      .connect_with(options)
+      .await
+  }
+
+  /// Closes our pool, has `replace` swap the file out while it's closed, then
+  /// opens (and migrates) whatever is there with the same options, key
+  /// included. Should `replace` or opening fail, `put_back` returns the file
+  /// as it was and that is opened again, so the app carries on with it; the
+  /// first error is returned. Commands that run meanwhile fail on the closed
+  /// pool. The SQL plugin's pool has to be closed around it, with `unshare`
+  /// and `reshare`.
+  pub async fn reopen(
+    &self,
+    replace: impl FnOnce(&Path) -> io::Result<()>,
+    put_back: impl FnOnce(&Path) -> io::Result<()>,
+  ) -> Result<(), AppError> {
+    let old = self.pool();
+    old.close().await;
+
+    let options = (*old.connect_options()).clone();
+    let path = options.get_filename().to_owned();
+    let opened = match replace(&path) {
+      Ok(()) => Self::connect(options.clone())
+        .await
+        .map_err(log_error("reopen database")),
+      Err(e) => Err(log_error("reopen database")(e)),
+    };
+    let error = match opened {
+      Ok(pool) => {
+        *self.0.write().unwrap() = pool;
+        return Ok(());
+      }
+      Err(error) => error,
+    };
+
+    put_back(&path).map_err(log_error("reopen database"))?;
+    let pool = Self::connect(options)
+      .await
+      .map_err(log_error("reopen database"))?;
+    *self.0.write().unwrap() = pool;
+    Err(error)
+  }
+
+  /// Takes the SQL plugin's pool on this database away and closes it, so
+  /// nothing has the file open during `reopen`. The frontend's queries fail
+  /// until `reshare`.
+  pub(crate) async fn unshare(&self, app: &AppHandle) {
+    let instances = app.state::<DbInstances>();
+    let shared = instances.0.write().await.remove(&self.url());
+    if let Some(DbPool::Sqlite(pool)) = shared {
+      pool.close().await;
+    }
+  }
+
+  /// Gives the SQL plugin a new pool on the file after `reopen`.
+  pub(crate) async fn reshare(&self, app: &AppHandle) {
+    let instances = app.state::<DbInstances>();
+    instances
+      .0
+      .write()
+      .await
+      .insert(self.url(), self.plugin_pool());
+  }
+
+  /// Registers the SQL plugin for this database, then makes the pool and
+  /// everything that depends on it available to commands.
+  pub fn install(self, app: &AppHandle) -> tauri::Result<()> {
+    app.plugin(tauri_plugin_sql::Builder::default().build())?;
+    self.share(app);
+    app.manage(self);
+
+    app.manage(History::default());
+    app.manage(Notes::default());
+    app.manage(Notified::default());
+    app.manage(reminders::spawn(app));
+    app.manage(backup::spawn(app));
+    Ok(())
+  }
+
+  /// Hands the SQL plugin a pool of its own on this database, under `url`,
+  /// for the frontend's `Database.get`. Left to `Database.load`, the plugin
+  /// would open the file itself, without the SQLCipher key, and run its own
+  /// migrations; this way its connections get the same options (key, foreign
+  /// keys) as ours, and the schema is only ever migrated by `open`. A separate
+  /// pool, because the plugin closes its pools on exit, before the pending
+  /// notes are flushed through ours.
+  fn share(&self, app: &AppHandle) {
+    let instances = app.state::<DbInstances>();
+    // nothing else has seen the plugin's state yet, so the lock is free
+    let Ok(mut pools) = instances.0.try_write() else {
+      log::error!("SQL plugin is busy, the frontend cannot open the database");
+      return;
+    };
+    pools.insert(self.url(), self.plugin_pool());
+  }
+
+  fn plugin_pool(&self) -> DbPool {
+    let options = (*self.pool().connect_options()).clone();
+    // no idle timeout or lifetime, which would need a reaper task, and `setup`
+    // runs outside the async runtime
+    let pool = SqlitePoolOptions::new()
+      .max_connections(MAX_CONNECTIONS)
+      .idle_timeout(None)
+      .max_lifetime(None)
+      .connect_lazy_with(options);
+    DbPool::Sqlite(pool)
+  }
+
+  /// Connection string for the SQL plugin / `Database.get`.
+  pub fn url(&self) -> String {
+    format!(
+      "sqlite:{}",
+      self.pool().connect_options().get_filename().display()
+    )
+  }
+}
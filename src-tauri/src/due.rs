@@ -0,0 +1,156 @@
+use chrono::{
+  DateTime, Datelike, Days, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc,
+  Weekday,
+};
+
+use crate::commands::parse_timestamp;
+use crate::error::{guard_sync, AppError};
+use crate::models::timestamp;
+
+/// Time of day for phrases that name only a day ("tomorrow", "friday"): the
+/// end of it, so a task due "today" isn't overdue the moment it's created.
+const END_OF_DAY: (u32, u32) = (23, 59);
+
+fn weekday(word: &str) -> Option<Weekday> {
+  Some(match word {
+    "monday" | "mon" => Weekday::Mon,
+    "tuesday" | "tue" | "tues" => Weekday::Tue,
+    "wednesday" | "wed" => Weekday::Wed,
+    "thursday" | "thu" | "thurs" => Weekday::Thu,
+    "friday" | "fri" => Weekday::Fri,
+    "saturday" | "sat" => Weekday::Sat,
+    "sunday" | "sun" => Weekday::Sun,
+    _ => return None,
+  })
+}
+
+/// Reads a leading day phrase; returns the date and how many words it used.
+fn day(words: &[&str], today: NaiveDate) -> Option<(NaiveDate, usize)> {
+  let (target, used) = match words {
+    ["today", ..] => return Some((today, 1)),
+    ["tomorrow", ..] => return Some((today + Days::new(1), 1)),
+    ["next" | "on", name, ..] => (weekday(name)?, 2),
+    [name, ..] => (weekday(name)?, 1),
+    [] => return None,
+  };
+  // always a day still to come: "monday" on a Monday means a week from now
+  let ahead = (7 + target.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+  let ahead = if ahead == 0 { 7 } else { ahead };
+  Some((today + Days::new(ahead.into()), used))
+}
+
+/// "3pm", "3:30 pm", "15:00", "noon", "midnight", optionally after "at".
+fn time(words: &[&str]) -> Option<NaiveTime> {
+  let words = match words {
+    ["at", rest @ ..] => rest,
+    rest => rest,
+  };
+  let text = words.concat();
+  match text.as_str() {
+    "" => return None,
+    "noon" => return NaiveTime::from_hms_opt(12, 0, 0),
+    "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+    _ => {}
+  }
+
+  let (clock, meridiem) = if let Some(clock) = text.strip_suffix("am") {
+    (clock, Some(0))
+  } else if let Some(clock) = text.strip_suffix("pm") {
+    (clock, Some(12))
+  } else {
+    (text.as_str(), None)
+  };
+  let (hour, minute) = match clock.split_once(':') {
+    Some((h, m)) => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+    // a bare "3" could be either half of the day; don't guess
+    None if meridiem.is_some() => (clock.parse::<u32>().ok()?, 0),
+    None => return None,
+  };
+  let hour = match meridiem {
+    Some(offset) if (1..=12).contains(&hour) => hour % 12 + offset,
+    Some(_) => return None,
+    None => hour,
+  };
+  NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// "in 2 hours", "in a day", "in 30 min".
+fn offset(amount: &str, unit: &str) -> Option<TimeDelta> {
+  let amount: i64 = match amount {
+    "a" | "an" => 1,
+    n => n.parse().ok().filter(|n| *n >= 0)?,
+  };
+  match unit.trim_end_matches('s') {
+    "minute" | "min" | "m" => TimeDelta::try_minutes(amount),
+    "hour" | "hr" | "h" => TimeDelta::try_hours(amount),
+    "day" | "d" => TimeDelta::try_days(amount),
+    "week" | "wk" | "w" => TimeDelta::try_weeks(amount),
+    _ => None,
+  }
+}
+
+/// `at` on the local wall clock; in a DST gap, the same time an hour later.
+pub(crate) fn local(at: NaiveDateTime) -> Option<DateTime<Utc>> {
+  Local
+    .from_local_datetime(&at)
+    .earliest()
+    .or_else(|| {
+      Local
+        .from_local_datetime(&(at + TimeDelta::hours(1)))
+        .earliest()
+    })
+    .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// When a task due some time on `date` (local) is due: `END_OF_DAY`.
+pub(crate) fn end_of_day(date: NaiveDate) -> Option<DateTime<Utc>> {
+  local(date.and_time(NaiveTime::from_hms_opt(END_OF_DAY.0, END_OF_DAY.1, 0)?))
+}
+
+fn parse(input: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+  let input = input.trim().to_lowercase();
+  let words: Vec<&str> = input.split_whitespace().collect();
+  match words.as_slice() {
+    ["now"] => return Some(now),
+    ["in", amount, unit] => return now.checked_add_signed(offset(amount, unit)?),
+    _ => {}
+  }
+
+  let today = now.with_timezone(&Local).date_naive();
+  match day(&words, today) {
+    Some((date, used)) => {
+      let at = match &words[used..] {
+        [] => return end_of_day(date),
+        rest => time(rest)?,
+      };
+      local(date.and_time(at))
+    }
+    // just a time: the next time the clock shows it
+    None => {
+      let at = time(&words)?;
+      let due = local(today.and_time(at))?;
+      if due > now {
+        Some(due)
+      } else {
+        local((today + Days::new(1)).and_time(at))
+      }
+    }
+  }
+}
+
+/// Turns phrases like "tomorrow 3pm", "next monday" or "in 2 hours" into an
+/// RFC3339 timestamp, relative to `now` (the system clock if omitted) in the
+/// local timezone. Anything else fails with `UnrecognizedDate`, rather than a
+/// guess.
+#[tauri::command]
+pub fn parse_due(input: String, now: Option<String>) -> Result<String, AppError> {
+  guard_sync("parse_due", || {
+    let now = match now {
+      Some(now) => parse_timestamp("now", &now)?,
+      None => Utc::now(),
+    };
+    parse(&input, now)
+      .map(|due| timestamp(&due))
+      .ok_or(AppError::UnrecognizedDate { input })
+  })
+}
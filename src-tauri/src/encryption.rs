@@ -0,0 +1,204 @@
+//! Opt-in SQLCipher encryption of the database file.
+//!
+//! The SQL plugin has no way to pass a key when it opens a database, so the
+//! frontend never has it open one: `AppDb::install` hands it a keyed pool,
+//! which is why the frontend can only reach the database once it's unlocked.
+
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::ConnectOptions;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{is_corruption, quote, AppDb};
+use crate::error::{guard, AppError};
+use crate::logging::log_error;
+use crate::notes::Notes;
+
+/// Managed when the database on disk is encrypted. Until `set_db_key`
+/// unlocks it there's no `AppDb`, so every other command fails.
+pub struct Locked(pub PathBuf);
+
+/// Whether the database at `path` is encrypted, going by the marker
+/// `apply_pending` leaves next to it. The file itself can't tell: SQLCipher
+/// pages look random, and so does a plaintext file with a damaged header,
+/// which has to go through the integrity check instead.
+pub fn is_encrypted(path: &Path) -> bool {
+  path.exists() && marker_path(path).exists()
+}
+
+/// Empty file next to an encrypted database.
+fn marker_path(path: &Path) -> PathBuf {
+  let mut marker = path.as_os_str().to_owned();
+  marker.push(".sqlcipher");
+  PathBuf::from(marker)
+}
+
+/// Where `set_db_key` writes the encrypted copy of a plaintext database.
+fn pending_path(path: &Path) -> PathBuf {
+  let mut pending = path.as_os_str().to_owned();
+  pending.push(".encrypted");
+  PathBuf::from(pending)
+}
+
+pub(crate) fn remove_if_exists(path: &Path) -> io::Result<()> {
+  match fs::remove_file(path) {
+    Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+    _ => Ok(()),
+  }
+}
+
+/// Replaces the plaintext database (and its `-wal`/`-shm` files, which hold
+/// plaintext too) with an encrypted copy left by `set_db_key`, if there is
+/// one, and marks it as encrypted. Must run before anything opens the
+/// database. The marker goes first: should the app stop in between, the copy
+/// is still pending and this runs again on the next start.
+pub fn apply_pending(path: &Path) -> io::Result<()> {
+  let pending = pending_path(path);
+  if !pending.exists() {
+    return Ok(());
+  }
+  for suffix in ["-wal", "-shm"] {
+    let mut side = path.as_os_str().to_owned();
+    side.push(suffix);
+    remove_if_exists(Path::new(&side))?;
+  }
+  let marker = marker_path(path);
+  File::create(&marker)?;
+  fs::rename(&pending, path).inspect_err(|_| {
+    // still the plaintext database
+    let _ = fs::remove_file(&marker);
+  })
+}
+
+/// Opens one connection with `key` and reads the schema, which is the first
+/// point SQLCipher can tell a wrong key from a right one.
+async fn check_key(path: &Path, key: &str) -> Result<(), AppError> {
+  let check = async {
+    let mut conn = SqliteConnectOptions::new()
+      .filename(path)
+      .pragma("key", quote(key))
+      .connect()
+      .await?;
+    sqlx::query("SELECT count(*) FROM sqlite_master;")
+      .execute(&mut conn)
+      .await
+  };
+  match check.await {
+    Ok(_) => Ok(()),
+    Err(e) if is_corruption(&e) => Err(AppError::InvalidPassphrase),
+    Err(e) => Err(log_error("check_key")(e)),
+  }
+}
+
+/// Whether the frontend has to ask for the passphrase before anything else.
+#[tauri::command]
+pub fn is_db_locked(app: AppHandle) -> bool {
+  app.try_state::<Locked>().is_some() && app.try_state::<AppDb>().is_none()
+}
+
+/// Unlocks an encrypted database with its passphrase, running migrations only
+/// once the key is in place. On a plaintext database this instead turns
+/// encryption on: an encrypted copy is written alongside, and the app
+/// restarts to switch over to it.
+#[tauri::command]
+pub async fn set_db_key(app: AppHandle, passphrase: String) -> Result<(), AppError> {
+  guard("set_db_key", async {
+    if passphrase.is_empty() {
+      return Err(AppError::validation("passphrase must not be empty"));
+    }
+
+    match (app.try_state::<Locked>(), app.try_state::<AppDb>()) {
+      (Some(_), Some(_)) => Err(AppError::validation("database is already unlocked")),
+      (Some(locked), None) => {
+        let path = locked.0.clone();
+        check_key(&path, &passphrase).await?;
+        let db = AppDb::open(&path, Some(&passphrase))
+          .await
+          .map_err(log_error("set_db_key"))?;
+        db.install(&app).map_err(log_error("set_db_key"))?;
+        log::info!("database unlocked");
+        Ok(())
+      }
+      (None, Some(db)) => {
+        // into the copy, which is all that's left after the restart
+        app.state::<Notes>().flush_all(&app).await;
+        let mut conn = db.pool().acquire().await.map_err(log_error("set_db_key"))?;
+        let cipher: Option<String> = sqlx::query_scalar("PRAGMA cipher_version;")
+          .fetch_optional(&mut *conn)
+          .await
+          .map_err(log_error("set_db_key"))?;
+        if cipher.is_none() {
+          return Err(AppError::validation(
+            "this build of Tasks does not support encryption",
+          ));
+        }
+
+        let path = db.pool().connect_options().get_filename().to_owned();
+        let pending = pending_path(&path);
+        remove_if_exists(&pending).map_err(log_error("set_db_key"))?;
+        sqlx::query("ATTACH DATABASE ? AS encrypted KEY ?;")
+          .bind(pending.to_string_lossy().into_owned())
+          .bind(&passphrase)
+          .execute(&mut *conn)
+          .await
+          .map_err(log_error("set_db_key"))?;
+        let exported = sqlx::query("SELECT sqlcipher_export('encrypted');")
+          .execute(&mut *conn)
+          .await;
+        sqlx::query("DETACH DATABASE encrypted;")
+          .execute(&mut *conn)
+          .await
+          .map_err(log_error("set_db_key"))?;
+        if let Err(e) = exported {
+          let _ = fs::remove_file(&pending);
+          return Err(log_error("set_db_key")(e));
+        }
+
+        log::info!("encrypted copy written, restarting to switch over");
+        app.restart()
+      }
+      (None, None) => Err(AppError::validation("database is not open")),
+    }
+  })
+  .await
+}
+
+/// Changes the passphrase of an encrypted database with `PRAGMA rekey`. The
+/// other pooled connections still hold the old key, so the app restarts and
+/// asks for the new one.
+#[tauri::command]
+pub async fn change_db_key(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  old: String,
+  new: String,
+) -> Result<(), AppError> {
+  guard("change_db_key", async {
+    if app.try_state::<Locked>().is_none() {
+      return Err(AppError::validation("database is not encrypted"));
+    }
+    if new.is_empty() {
+      return Err(AppError::validation("passphrase must not be empty"));
+    }
+
+    let path = db.pool().connect_options().get_filename().to_owned();
+    check_key(&path, &old).await?;
+    // before the restart, and while every connection still has the key
+    app.state::<Notes>().flush_all(&app).await;
+    let mut conn = db
+      .pool()
+      .acquire()
+      .await
+      .map_err(log_error("change_db_key"))?;
+    sqlx::query(&format!("PRAGMA rekey = {};", quote(&new)))
+      .execute(&mut *conn)
+      .await
+      .map_err(log_error("change_db_key"))?;
+
+    log::info!("database key changed, restarting");
+    app.restart()
+  })
+  .await
+}
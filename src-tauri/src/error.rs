@@ -0,0 +1,123 @@
+use serde::Serialize;
+use std::any::Any;
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::pin;
+use std::task::Poll;
+
+/// Why a command failed. Serialized with a `kind` the frontend can switch on
+/// (`not_found`, `validation`, `unrecognized_date`, `conflict`,
+/// `invalid_passphrase`, `db`, `internal`) and, where there is one, a
+/// `message`.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum AppError {
+  /// The task (or list) the command was asked to act on doesn't exist.
+  NotFound,
+  /// The input was rejected; the message says why and can be shown as is.
+  Validation(String),
+  /// `parse_due` couldn't make a date of `input`; offer a date picker instead.
+  UnrecognizedDate { input: String },
+  /// The task changed since the caller read it; reload and try again.
+  Conflict,
+  /// The passphrase doesn't unlock the encrypted database; ask again.
+  InvalidPassphrase,
+  /// Something failed on our side. The message is for logs and bug reports.
+  Db(String),
+  /// The command panicked (a bug); the message is the panic's.
+  Internal(String),
+}
+
+impl AppError {
+  pub fn validation(message: impl Into<String>) -> Self {
+    Self::Validation(message.into())
+  }
+}
+
+impl fmt::Display for AppError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      AppError::NotFound => f.write_str("not found"),
+      AppError::Validation(message) | AppError::Db(message) => f.write_str(message),
+      AppError::UnrecognizedDate { input } => write!(f, "unrecognized due date: {input:?}"),
+      AppError::Internal(message) => write!(f, "internal error: {message}"),
+      AppError::Conflict => f.write_str("conflict"),
+      AppError::InvalidPassphrase => f.write_str("invalid passphrase"),
+    }
+  }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<sqlx::Error> for AppError {
+  fn from(e: sqlx::Error) -> Self {
+    match e {
+      sqlx::Error::RowNotFound => AppError::NotFound,
+      e => AppError::Db(e.to_string()),
+    }
+  }
+}
+
+impl From<io::Error> for AppError {
+  fn from(e: io::Error) -> Self {
+    AppError::Db(e.to_string())
+  }
+}
+
+impl From<serde_json::Error> for AppError {
+  fn from(e: serde_json::Error) -> Self {
+    AppError::Db(e.to_string())
+  }
+}
+
+impl From<csv::Error> for AppError {
+  fn from(e: csv::Error) -> Self {
+    AppError::Db(e.to_string())
+  }
+}
+
+impl From<tauri::Error> for AppError {
+  fn from(e: tauri::Error) -> Self {
+    AppError::Db(e.to_string())
+  }
+}
+
+fn panicked(op: &str, payload: Box<dyn Any + Send>) -> AppError {
+  let message = payload
+    .downcast_ref::<&str>()
+    .map(|s| s.to_string())
+    .or_else(|| payload.downcast_ref::<String>().cloned())
+    .unwrap_or_else(|| "unknown panic".to_string());
+  log::error!("{op} panicked: {message}");
+  AppError::Internal(message)
+}
+
+/// Runs a command's body, turning a panic inside it into `AppError::Internal`
+/// instead of a call the frontend waits on forever. Wrap every command in it.
+pub(crate) async fn guard<T>(
+  op: &'static str,
+  body: impl Future<Output = Result<T, AppError>>,
+) -> Result<T, AppError> {
+  let mut body = pin!(body);
+  let caught =
+    poll_fn(
+      |cx| match panic::catch_unwind(AssertUnwindSafe(|| body.as_mut().poll(cx))) {
+        Ok(Poll::Ready(result)) => Poll::Ready(Ok(result)),
+        Ok(Poll::Pending) => Poll::Pending,
+        Err(payload) => Poll::Ready(Err(payload)),
+      },
+    )
+    .await;
+  caught.unwrap_or_else(|payload| Err(panicked(op, payload)))
+}
+
+/// `guard` for synchronous commands, which run on the main thread: a panic
+/// there would take the whole app down.
+pub(crate) fn guard_sync<T>(
+  op: &'static str,
+  body: impl FnOnce() -> Result<T, AppError>,
+) -> Result<T, AppError> {
+  panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|payload| Err(panicked(op, payload)))
+}
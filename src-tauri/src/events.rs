@@ -0,0 +1,45 @@
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+pub const TASK_CHANGED: &str = "task-changed";
+pub const TASKS_CHANGED: &str = "tasks-changed";
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+  Created,
+  Updated,
+  Deleted,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TaskChanged {
+  pub id: i64,
+  pub kind: ChangeKind,
+}
+
+/// One event for a batch operation, instead of one `task-changed` per row.
+#[derive(Serialize, Clone, Debug)]
+pub struct TasksChanged {
+  pub ids: Vec<i64>,
+  pub kind: ChangeKind,
+}
+
+/// Broadcast a change to every window. Call only after the write has been
+/// committed so listeners that refetch never read stale rows.
+pub fn task_changed(app: &AppHandle, id: i64, kind: ChangeKind) {
+  log::info!("task {id} {kind:?}");
+  if let Err(e) = app.emit(TASK_CHANGED, TaskChanged { id, kind }) {
+    log::warn!("failed to emit {TASK_CHANGED} for task {id}: {e}");
+  }
+}
+
+pub fn tasks_changed(app: &AppHandle, ids: Vec<i64>, kind: ChangeKind) {
+  if ids.is_empty() {
+    return;
+  }
+  log::info!("{} tasks {kind:?}: {ids:?}", ids.len());
+  if let Err(e) = app.emit(TASKS_CHANGED, TasksChanged { ids, kind }) {
+    log::warn!("failed to emit {TASKS_CHANGED}: {e}");
+  }
+}
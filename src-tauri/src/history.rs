@@ -0,0 +1,406 @@
+use chrono::Utc;
+use sqlx::{FromRow, SqliteConnection};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, State};
+
+use crate::attachments::Attachment;
+use crate::checklist::ChecklistItem;
+use crate::commands::placeholders;
+use crate::db::{with_retry, AppDb, TxError};
+use crate::error::{guard, AppError};
+use crate::events::{self, ChangeKind};
+use crate::logging::log_error;
+use crate::models::{timestamp, TASK_TAGS};
+use crate::tags;
+
+/// How many operations `undo_last` can step back through.
+const MAX_UNDO: usize = 50;
+
+/// Every column of a task row, so it can be put back exactly as it was.
+const SNAPSHOT_COLUMNS: &str = "id, title, notes, done, list_id, accumulated_seconds, \
+   running_since, created_at, due, deleted_at, repeat, priority, parent_id, completed_at, \
+   sort_order, archived";
+
+/// A task's checklist items and attachments as JSON arrays (append after
+/// `TASK_TAGS`). Deleting a task takes them with it, so they come back with it.
+const SNAPSHOT_CHILDREN: &str = "(SELECT json_group_array(json_object( \
+       'id', id, 'task_id', task_id, 'text', text, \
+       'done', json(CASE WHEN done THEN 'true' ELSE 'false' END), 'position', position)) \
+     FROM (SELECT * FROM checklist_items WHERE task_id = tasks.id ORDER BY position, id) \
+   ) AS checklist, \
+   (SELECT json_group_array(json_object( \
+       'id', id, 'task_id', task_id, 'file_name', file_name, 'path', path, 'size', size, \
+       'modified_at', modified_at, 'attached_at', attached_at)) \
+     FROM (SELECT * FROM attachments WHERE task_id = tasks.id ORDER BY id) \
+   ) AS attachments";
+
+#[derive(FromRow, Clone, Debug, PartialEq)]
+struct Snapshot {
+  id: i64,
+  title: String,
+  notes: Option<String>,
+  done: bool,
+  list_id: Option<i64>,
+  accumulated_seconds: i64,
+  running_since: Option<String>,
+  created_at: Option<String>,
+  due: Option<String>,
+  deleted_at: Option<String>,
+  repeat: String,
+  priority: i64,
+  parent_id: Option<i64>,
+  completed_at: Option<String>,
+  sort_order: f64,
+  archived: bool,
+  /// Tag names as a JSON array.
+  tags: String,
+  /// `ChecklistItem`s and `Attachment`s as JSON arrays.
+  checklist: String,
+  attachments: String,
+}
+
+/// One task before and after an operation; `None` where it didn't exist.
+#[derive(Debug)]
+struct Change {
+  id: i64,
+  before: Option<Snapshot>,
+  after: Option<Snapshot>,
+}
+
+/// Everything one mutating command changed, undone or redone as a unit.
+#[derive(Debug)]
+pub struct UndoEntry {
+  label: &'static str,
+  changes: Vec<Change>,
+}
+
+#[derive(Default)]
+struct Stacks {
+  undo: Vec<UndoEntry>,
+  redo: Vec<UndoEntry>,
+}
+
+/// Undo/redo stacks for this session, newest last. Managed state.
+#[derive(Default)]
+pub struct History(Mutex<Stacks>);
+
+impl History {
+  /// Pushes a finished operation; any redo history is gone from here on.
+  pub(crate) fn record(&self, entry: UndoEntry) {
+    if entry.changes.is_empty() {
+      return;
+    }
+    let mut stacks = self.0.lock().unwrap();
+    stacks.undo.push(entry);
+    if stacks.undo.len() > MAX_UNDO {
+      stacks.undo.remove(0);
+    }
+    stacks.redo.clear();
+  }
+
+  /// Forgets everything, for changes too big to step back through (imports).
+  pub(crate) fn clear(&self) {
+    let mut stacks = self.0.lock().unwrap();
+    stacks.undo.clear();
+    stacks.redo.clear();
+  }
+}
+
+async fn load(conn: &mut SqliteConnection, ids: &[i64]) -> sqlx::Result<HashMap<i64, Snapshot>> {
+  if ids.is_empty() {
+    return Ok(HashMap::new());
+  }
+  let sql = format!(
+    "SELECT {SNAPSHOT_COLUMNS}, {TASK_TAGS}, {SNAPSHOT_CHILDREN} FROM tasks WHERE id IN ({});",
+    placeholders(ids.len())
+  );
+  let mut query = sqlx::query_as::<_, Snapshot>(&sql);
+  for id in ids {
+    query = query.bind(id);
+  }
+  let rows = query.fetch_all(&mut *conn).await?;
+  Ok(rows.into_iter().map(|row| (row.id, row)).collect())
+}
+
+/// Collects the "before" side of an operation inside its transaction: call
+/// `track` with every task the command is about to touch, `created` for rows
+/// it inserts, then `finish` once it's done writing.
+pub(crate) struct Recorder {
+  label: &'static str,
+  ids: Vec<i64>,
+  before: HashMap<i64, Option<Snapshot>>,
+}
+
+impl Recorder {
+  pub(crate) async fn start(
+    conn: &mut SqliteConnection,
+    label: &'static str,
+    ids: &[i64],
+  ) -> sqlx::Result<Self> {
+    let mut recorder = Self {
+      label,
+      ids: Vec::new(),
+      before: HashMap::new(),
+    };
+    recorder.track(conn, ids).await?;
+    Ok(recorder)
+  }
+
+  /// Snapshots `ids` as they are now. Must come before the command changes
+  /// them; ids already tracked keep their first snapshot.
+  pub(crate) async fn track(
+    &mut self,
+    conn: &mut SqliteConnection,
+    ids: &[i64],
+  ) -> sqlx::Result<()> {
+    let new: Vec<i64> = ids
+      .iter()
+      .copied()
+      .filter(|id| !self.before.contains_key(id))
+      .collect();
+    let mut rows = load(conn, &new).await?;
+    for id in new {
+      self.before.insert(id, rows.remove(&id));
+      self.ids.push(id);
+    }
+    Ok(())
+  }
+
+  /// A row the command inserted, which undo removes again.
+  pub(crate) fn created(&mut self, id: i64) {
+    if let Entry::Vacant(slot) = self.before.entry(id) {
+      slot.insert(None);
+      self.ids.push(id);
+    }
+  }
+
+  pub(crate) async fn finish(mut self, conn: &mut SqliteConnection) -> sqlx::Result<UndoEntry> {
+    let mut after = load(conn, &self.ids).await?;
+    let changes = self
+      .ids
+      .iter()
+      .map(|&id| Change {
+        id,
+        before: self.before.remove(&id).flatten(),
+        after: after.remove(&id),
+      })
+      .filter(|change| change.before != change.after)
+      .collect();
+    Ok(UndoEntry {
+      label: self.label,
+      changes,
+    })
+  }
+}
+
+/// Puts every task in `changes` back to one side of the change: `before`
+/// for undo, `after` for redo.
+async fn apply(conn: &mut SqliteConnection, changes: &[Change], undo: bool) -> Result<(), TxError> {
+  // rows come back in any order, so a child may be restored before its parent
+  sqlx::query("PRAGMA defer_foreign_keys = ON;")
+    .execute(&mut *conn)
+    .await?;
+  let now = timestamp(&Utc::now());
+  for change in changes {
+    let target = if undo { &change.before } else { &change.after };
+    let Some(row) = target else {
+      sqlx::query("DELETE FROM tasks WHERE id = ?;")
+        .bind(change.id)
+        .execute(&mut *conn)
+        .await?;
+      continue;
+    };
+
+    sqlx::query(&format!(
+      "INSERT INTO tasks ({SNAPSHOT_COLUMNS}, updated_at) \
+       VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) \
+       ON CONFLICT(id) DO UPDATE SET \
+         title = excluded.title, notes = excluded.notes, done = excluded.done, \
+         list_id = excluded.list_id, accumulated_seconds = excluded.accumulated_seconds, \
+         running_since = excluded.running_since, created_at = excluded.created_at, \
+         due = excluded.due, deleted_at = excluded.deleted_at, repeat = excluded.repeat, \
+         priority = excluded.priority, parent_id = excluded.parent_id, \
+         completed_at = excluded.completed_at, sort_order = excluded.sort_order, \
+         archived = excluded.archived, updated_at = excluded.updated_at;"
+    ))
+    .bind(row.id)
+    .bind(&row.title)
+    .bind(&row.notes)
+    .bind(row.done)
+    .bind(row.list_id)
+    .bind(row.accumulated_seconds)
+    .bind(&row.running_since)
+    .bind(&row.created_at)
+    .bind(&row.due)
+    .bind(&row.deleted_at)
+    .bind(&row.repeat)
+    .bind(row.priority)
+    .bind(row.parent_id)
+    .bind(&row.completed_at)
+    .bind(row.sort_order)
+    .bind(row.archived)
+    // going back is still a change, as far as conflict checks go
+    .bind(&now)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query("DELETE FROM task_tags WHERE task_id = ?;")
+      .bind(row.id)
+      .execute(&mut *conn)
+      .await?;
+    let names: Vec<String> = serde_json::from_str(&row.tags).map_err(log_error("apply"))?;
+    for name in names {
+      tags::attach(conn, row.id, &name).await?;
+    }
+
+    // back with their own ids, which AUTOINCREMENT never hands out twice
+    sqlx::query("DELETE FROM checklist_items WHERE task_id = ?;")
+      .bind(row.id)
+      .execute(&mut *conn)
+      .await?;
+    let items: Vec<ChecklistItem> =
+      serde_json::from_str(&row.checklist).map_err(log_error("apply"))?;
+    for item in items {
+      sqlx::query(
+        "INSERT INTO checklist_items (id, task_id, text, done, position) VALUES (?, ?, ?, ?, ?);",
+      )
+      .bind(item.id)
+      .bind(row.id)
+      .bind(&item.text)
+      .bind(item.done)
+      .bind(item.position)
+      .execute(&mut *conn)
+      .await?;
+    }
+    sqlx::query("DELETE FROM attachments WHERE task_id = ?;")
+      .bind(row.id)
+      .execute(&mut *conn)
+      .await?;
+    let attachments: Vec<Attachment> =
+      serde_json::from_str(&row.attachments).map_err(log_error("apply"))?;
+    for attachment in attachments {
+      sqlx::query(
+        "INSERT INTO attachments \
+           (id, task_id, file_name, path, size, modified_at, attached_at) \
+         VALUES (?, ?, ?, ?, ?, ?, ?);",
+      )
+      .bind(attachment.id)
+      .bind(row.id)
+      .bind(&attachment.file_name)
+      .bind(&attachment.path)
+      .bind(attachment.size)
+      .bind(attachment.modified_at.as_ref().map(timestamp))
+      .bind(timestamp(&attachment.attached_at))
+      .execute(&mut *conn)
+      .await?;
+    }
+  }
+  Ok(())
+}
+
+/// What an undo or redo did: the operation's label, and the tasks it
+/// brought back, changed and removed.
+#[derive(Debug)]
+pub struct Stepped {
+  pub label: &'static str,
+  pub created: Vec<i64>,
+  pub updated: Vec<i64>,
+  pub deleted: Vec<i64>,
+}
+
+impl Stepped {
+  fn new(entry: &UndoEntry, undo: bool) -> Self {
+    let mut stepped = Self {
+      label: entry.label,
+      created: Vec::new(),
+      updated: Vec::new(),
+      deleted: Vec::new(),
+    };
+    for change in &entry.changes {
+      let (from, to) = if undo {
+        (&change.after, &change.before)
+      } else {
+        (&change.before, &change.after)
+      };
+      match (from, to) {
+        (_, None) => stepped.deleted.push(change.id),
+        (None, Some(_)) => stepped.created.push(change.id),
+        (Some(_), Some(_)) => stepped.updated.push(change.id),
+      }
+    }
+    stepped
+  }
+
+  fn notify(self, app: &AppHandle) -> String {
+    events::tasks_changed(app, self.created, ChangeKind::Created);
+    events::tasks_changed(app, self.updated, ChangeKind::Updated);
+    events::tasks_changed(app, self.deleted, ChangeKind::Deleted);
+    self.label.to_string()
+  }
+}
+
+/// What `undo_last` (or, with `undo` unset, `redo_last`) does, minus the
+/// change events; takes the pool directly, so it runs without an app
+/// (integration tests).
+pub async fn step(db: &AppDb, history: &History, undo: bool) -> Result<Option<Stepped>, AppError> {
+  let popped = {
+    let mut stacks = history.0.lock().unwrap();
+    if undo {
+      stacks.undo.pop()
+    } else {
+      stacks.redo.pop()
+    }
+  };
+  let Some(entry) = popped else {
+    return Ok(None);
+  };
+
+  let result = with_retry("step", || async {
+    let mut tx = db.pool().begin().await?;
+    apply(&mut tx, &entry.changes, undo).await?;
+    tx.commit().await?;
+    Ok(())
+  })
+  .await;
+
+  let stepped = Stepped::new(&entry, undo);
+  let changes_ok = result.is_ok();
+  // on failure the entry goes back where it came from
+  let mut stacks = history.0.lock().unwrap();
+  match (undo, changes_ok) {
+    (true, true) | (false, false) => stacks.redo.push(entry),
+    (false, true) | (true, false) => stacks.undo.push(entry),
+  }
+  result.map(|()| Some(stepped))
+}
+
+/// Reverts the most recent change made through a command; returns what it
+/// was (e.g. `delete_task`), or `None` when there's nothing left to undo.
+#[tauri::command]
+pub async fn undo_last(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+) -> Result<Option<String>, AppError> {
+  guard("undo_last", async {
+    let stepped = step(&db, &history, true).await?;
+    Ok(stepped.map(|s| s.notify(&app)))
+  })
+  .await
+}
+
+/// Re-applies the most recently undone change.
+#[tauri::command]
+pub async fn redo_last(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+) -> Result<Option<String>, AppError> {
+  guard("redo_last", async {
+    let stepped = step(&db, &history, false).await?;
+    Ok(stepped.map(|s| s.notify(&app)))
+  })
+  .await
+}
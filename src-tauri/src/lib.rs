@@ -1,3 +1,29 @@
+pub mod agenda;
+pub mod attachments;
+pub mod backup;
+pub mod checklist;
+pub mod commands;
+pub mod db;
+pub mod due;
+pub mod encryption;
+pub mod error;
+pub mod events;
+pub mod history;
+pub mod lists;
+pub mod logging;
+pub mod migrations;
+pub mod models;
+pub mod notes;
+pub mod reminders;
+pub mod settings;
+pub mod stats;
+pub mod streak;
+pub mod sync;
+pub mod tags;
+pub mod todoist;
+pub mod transfer;
+pub mod views;
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
   tauri::Builder::default()
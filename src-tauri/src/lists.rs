@@ -0,0 +1,208 @@
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::{FromRow, SqliteConnection};
+use tauri::{AppHandle, State};
+
+use crate::commands::descendants;
+use crate::db::{with_retry, AppDb};
+use crate::error::{guard, AppError};
+use crate::events::{self, ChangeKind};
+use crate::history::{History, Recorder};
+use crate::logging::log_error;
+use crate::models::{timestamp, Task, TASK_COLUMNS};
+
+/// A task list, as the frontend's space/folder tree shows it.
+#[derive(Serialize, FromRow, Clone, Debug)]
+pub struct TaskList {
+  pub id: i64,
+  pub name: String,
+  pub space_id: i64,
+  pub folder_id: Option<i64>,
+  /// Tasks in the list, not counting the trash.
+  pub task_count: i64,
+}
+
+/// The first space, created the way the frontend would if there is none.
+pub(crate) async fn default_space(conn: &mut SqliteConnection) -> sqlx::Result<i64> {
+  let existing: Option<i64> = sqlx::query_scalar("SELECT id FROM spaces ORDER BY id LIMIT 1;")
+    .fetch_optional(&mut *conn)
+    .await?;
+  match existing {
+    Some(id) => Ok(id),
+    None => {
+      sqlx::query_scalar("INSERT INTO spaces (name) VALUES ('My Space') RETURNING id;")
+        .fetch_one(&mut *conn)
+        .await
+    }
+  }
+}
+
+/// Adds a list at the top level of `space_id` (the first space by default).
+#[tauri::command]
+pub async fn create_list(
+  db: State<'_, AppDb>,
+  name: String,
+  space_id: Option<i64>,
+) -> Result<TaskList, AppError> {
+  guard("create_list", async {
+    let name = name.trim();
+    if name.is_empty() {
+      return Err(AppError::validation("list name must not be empty"));
+    }
+
+    with_retry("create_list", || async {
+      let mut tx = db.pool().begin().await?;
+      let space_id = match space_id {
+        Some(id) => id,
+        None => default_space(&mut tx).await?,
+      };
+      let list = sqlx::query_as::<_, TaskList>(
+        "INSERT INTO lists (space_id, folder_id, name) \
+         SELECT id, NULL, ? FROM spaces WHERE id = ? \
+         RETURNING id, name, space_id, folder_id, 0 AS task_count;",
+      )
+      .bind(name)
+      .bind(space_id)
+      .fetch_optional(&mut *tx)
+      .await?
+      .ok_or_else(|| AppError::validation("space not found"))?;
+      tx.commit().await?;
+      Ok(list)
+    })
+    .await
+  })
+  .await
+}
+
+#[tauri::command]
+pub async fn list_lists(db: State<'_, AppDb>) -> Result<Vec<TaskList>, AppError> {
+  guard("list_lists", async {
+    sqlx::query_as::<_, TaskList>(
+      "SELECT l.id, l.name, l.space_id, l.folder_id, \
+         (SELECT COUNT(*) FROM tasks t WHERE t.list_id = l.id AND t.deleted_at IS NULL) \
+           AS task_count \
+       FROM lists l ORDER BY l.space_id, l.folder_id IS NOT NULL, l.folder_id, l.id;",
+    )
+    .fetch_all(&db.pool())
+    .await
+    .map_err(log_error("list_lists"))
+  })
+  .await
+}
+
+/// Moves a task, with its subtasks, to another list.
+#[tauri::command]
+pub async fn move_task(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  task_id: i64,
+  list_id: i64,
+) -> Result<Task, AppError> {
+  guard("move_task", async {
+    let (task, subtasks, entry) = with_retry("move_task", || async {
+      let mut tx = db.pool().begin().await?;
+      let exists: Option<i64> = sqlx::query_scalar("SELECT id FROM lists WHERE id = ?;")
+        .bind(list_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+      if exists.is_none() {
+        return Err(AppError::validation("list not found").into());
+      }
+
+      let subtasks = descendants(&mut tx, task_id).await?;
+      let mut tracked = vec![task_id];
+      tracked.extend(&subtasks);
+      let undo = Recorder::start(&mut tx, "move_task", &tracked).await?;
+
+      let now = timestamp(&Utc::now());
+      let task = sqlx::query_as::<_, Task>(&format!(
+        "UPDATE tasks SET list_id = ?, updated_at = ? WHERE id = ? RETURNING {TASK_COLUMNS};"
+      ))
+      .bind(list_id)
+      .bind(&now)
+      .bind(task_id)
+      .fetch_optional(&mut *tx)
+      .await?
+      .ok_or(AppError::NotFound)?;
+      for id in &subtasks {
+        sqlx::query("UPDATE tasks SET list_id = ?, updated_at = ? WHERE id = ?;")
+          .bind(list_id)
+          .bind(&now)
+          .bind(id)
+          .execute(&mut *tx)
+          .await?;
+      }
+      let entry = undo.finish(&mut tx).await?;
+      tx.commit().await?;
+      Ok((task, subtasks, entry))
+    })
+    .await?;
+    history.record(entry);
+
+    events::task_changed(&app, task_id, ChangeKind::Updated);
+    events::tasks_changed(&app, subtasks, ChangeKind::Updated);
+    Ok(task)
+  })
+  .await
+}
+
+/// Deletes a list. Refuses while it still has tasks, unless `force` is set,
+/// in which case they move to the default (first remaining) list. Trashed
+/// tasks left in the list are deleted with it.
+#[tauri::command]
+pub async fn delete_list(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  id: i64,
+  force: Option<bool>,
+) -> Result<(), AppError> {
+  guard("delete_list", async {
+    let live = with_retry("delete_list", || async {
+      let mut tx = db.pool().begin().await?;
+      let live: Vec<i64> =
+        sqlx::query_scalar("SELECT id FROM tasks WHERE list_id = ? AND deleted_at IS NULL;")
+          .bind(id)
+          .fetch_all(&mut *tx)
+          .await?;
+
+      if !live.is_empty() {
+        if !force.unwrap_or(false) {
+          return Err(AppError::Validation(format!("list still has {} tasks", live.len())).into());
+        }
+        let fallback: i64 =
+          sqlx::query_scalar("SELECT id FROM lists WHERE id != ? ORDER BY id LIMIT 1;")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| AppError::validation("there is no other list to move its tasks to"))?;
+        sqlx::query(
+          "UPDATE tasks SET list_id = ?, updated_at = ? WHERE list_id = ? AND deleted_at IS NULL;",
+        )
+        .bind(fallback)
+        .bind(timestamp(&Utc::now()))
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+      }
+
+      let result = sqlx::query("DELETE FROM lists WHERE id = ?;")
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+      if result.rows_affected() == 0 {
+        return Err(AppError::NotFound.into());
+      }
+      tx.commit().await?;
+      Ok(live)
+    })
+    .await?;
+    // undo can't bring the list back, so entries pointing into it are useless
+    history.clear();
+
+    events::tasks_changed(&app, live, ChangeKind::Updated);
+    Ok(())
+  })
+  .await
+}
@@ -0,0 +1,63 @@
+use std::path::{Path, PathBuf};
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, Manager, Runtime};
+use tauri_plugin_log::{RotationStrategy, Target, TargetKind, TimezoneStrategy};
+
+use crate::error::{guard_sync, AppError};
+
+/// Log file name, without the `.log` the plugin appends.
+const LOG_NAME: &str = "tasks";
+
+/// Size at which the log starts over, so it can't grow without bound.
+const MAX_LOG_BYTES: u128 = 5 * 1024 * 1024;
+
+/// Where the log lives inside the app-data dir.
+pub fn log_file(data_dir: &Path) -> PathBuf {
+  data_dir.join("logs").join(format!("{LOG_NAME}.log"))
+}
+
+/// Timestamped log written under `data_dir` (and to stdout, for `tauri dev`).
+pub fn plugin<R: Runtime>(data_dir: &Path) -> TauriPlugin<R> {
+  let level = if cfg!(debug_assertions) {
+    log::LevelFilter::Debug
+  } else {
+    log::LevelFilter::Info
+  };
+  tauri_plugin_log::Builder::new()
+    .clear_targets()
+    .target(Target::new(TargetKind::Folder {
+      path: data_dir.join("logs"),
+      file_name: Some(LOG_NAME.into()),
+    }))
+    .target(Target::new(TargetKind::Stdout))
+    .level(level)
+    .max_file_size(MAX_LOG_BYTES)
+    .rotation_strategy(RotationStrategy::KeepOne)
+    .timezone_strategy(TimezoneStrategy::UseLocal)
+    .build()
+}
+
+/// `map_err` adapter for commands: converts the failure to an `AppError`,
+/// logging it with the operation it came from unless the caller is to blame
+/// (a missing row, say).
+pub(crate) fn log_error<E: Into<AppError>>(op: &'static str) -> impl Fn(E) -> AppError {
+  move |e| {
+    let e = e.into();
+    if let AppError::Db(message) = &e {
+      log::error!("{op}: {message}");
+    }
+    e
+  }
+}
+
+/// Full path of the log file, for attaching to bug reports.
+#[tauri::command]
+pub fn get_log_path(app: AppHandle) -> Result<String, AppError> {
+  guard_sync("get_log_path", || {
+    let dir = app
+      .path()
+      .app_data_dir()
+      .map_err(log_error("get_log_path"))?;
+    Ok(log_file(&dir).display().to_string())
+  })
+}
@@ -1,12 +1,230 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use tauri::Manager;
-use tauri_plugin_sql; // 👈 add this
+use app_lib::agenda;
+use app_lib::attachments;
+use app_lib::backup::{self, Backups};
+use app_lib::checklist;
+use app_lib::commands;
+use app_lib::db::{self, AppDb};
+use app_lib::due;
+use app_lib::encryption::{self, Locked};
+use app_lib::history;
+use app_lib::lists;
+use app_lib::logging;
+use app_lib::notes::{self, Notes};
+use app_lib::reminders::Reminders;
+use app_lib::settings;
+use app_lib::stats;
+use app_lib::streak;
+use app_lib::sync;
+use app_lib::tags;
+use app_lib::todoist;
+use app_lib::transfer;
+use app_lib::views::{self, Views};
+use std::fs;
+use tauri::{AppHandle, Manager, RunEvent, WindowEvent};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
 
 fn main() {
   tauri::Builder::default()
-    // 👇 register the SQL plugin
-    .plugin(tauri_plugin_sql::Builder::default().build())
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
-}
\ No newline at end of file
+    .plugin(tauri_plugin_dialog::init())
+    .plugin(tauri_plugin_notification::init())
+    .manage(Views::default())
+    .on_window_event(|window, event| {
+      if let WindowEvent::CloseRequested { .. } = event {
+        window.state::<Views>().forget(window.label());
+      }
+    })
+    .setup(|app| {
+      let data_dir = match app.path().app_data_dir() {
+        Ok(dir) => fs::create_dir_all(&dir)
+          .map(|_| dir)
+          .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+      };
+      let data_dir = match data_dir {
+        Ok(dir) => dir,
+        Err(e) => {
+          fatal(
+            app.handle(),
+            format!("Tasks could not create its data folder, so it cannot open your tasks.\n\n{e}"),
+          );
+          return Ok(());
+        }
+      };
+
+      app.handle().plugin(logging::plugin(&data_dir))?;
+      log::info!("starting Tasks {}", app.package_info().version);
+
+      let db_path = data_dir.join("tasks.db");
+      // Earlier builds let the SQL plugin resolve `tasks.db` against the config
+      // dir; carry that file over once (it's the same dir on Windows/macOS).
+      if let Ok(old_path) = app.path().app_config_dir().map(|dir| dir.join("tasks.db")) {
+        if !db_path.exists() && old_path.exists() {
+          if let Err(e) = fs::rename(&old_path, &db_path) {
+            log::warn!(
+              "could not move {} to {}: {e}",
+              old_path.display(),
+              db_path.display()
+            );
+          }
+        }
+      }
+
+      // a `restore_backup` the app quit in the middle of
+      if let Err(e) = backup::recover_restore(&db_path) {
+        log::error!("could not undo the unfinished restore: {e}");
+      }
+      // `set_db_key` leaves an encrypted copy to take over on the next start
+      if let Err(e) = encryption::apply_pending(&db_path) {
+        log::error!("could not switch to the encrypted database: {e}");
+      }
+      if encryption::is_encrypted(&db_path) {
+        // opened by `set_db_key` once the frontend has the passphrase
+        log::info!("database is encrypted, waiting for the passphrase");
+        app.manage(Locked(db_path));
+        return Ok(());
+      }
+
+      // a crash mid-write can leave the file unreadable; start over with an
+      // empty database rather than refusing to launch
+      let quarantined = match tauri::async_runtime::block_on(db::is_intact(&db_path)) {
+        Ok(true) => None,
+        Ok(false) => match db::quarantine(&db_path) {
+          Ok(moved) => {
+            log::error!(
+              "database failed its integrity check, moved to {}",
+              moved.display()
+            );
+            Some(moved)
+          }
+          Err(e) => {
+            log::error!("database failed its integrity check and could not be moved: {e}");
+            fatal(
+              app.handle(),
+              format!("Your tasks database is damaged and could not be moved aside.\n\n{e}"),
+            );
+            return Ok(());
+          }
+        },
+        Err(e) => {
+          log::warn!("could not check {}: {e}", db_path.display());
+          None
+        }
+      };
+
+      let db = match tauri::async_runtime::block_on(AppDb::open(&db_path, None)) {
+        Ok(db) => db,
+        Err(e) => {
+          log::error!("could not open {}: {e}", db_path.display());
+          fatal(
+            app.handle(),
+            format!("Tasks could not open its database.\n\n{e}"),
+          );
+          return Ok(());
+        }
+      };
+      db.install(app.handle())?;
+
+      if let Some(moved) = quarantined {
+        app
+          .dialog()
+          .message(format!(
+            "Your tasks database was damaged and could not be read, so Tasks started \
+             with an empty one.\n\nThe damaged file was kept at:\n{}",
+            moved.display()
+          ))
+          .title("Tasks")
+          .kind(MessageDialogKind::Warning)
+          .show(|_| {});
+      }
+      Ok(())
+    })
+    .invoke_handler(tauri::generate_handler![
+      commands::db_url,
+      encryption::is_db_locked,
+      encryption::set_db_key,
+      encryption::change_db_key,
+      logging::get_log_path,
+      backup::backup_now,
+      backup::list_backups,
+      backup::restore_backup,
+      commands::create_task,
+      commands::list_tasks,
+      commands::list_tasks_paged,
+      commands::get_task,
+      notes::save_notes,
+      sync::tasks_changed_since,
+      commands::toggle_task_done,
+      commands::delete_task,
+      commands::restore_task,
+      commands::bulk_complete,
+      commands::search_tasks,
+      commands::set_priority,
+      commands::update_task,
+      commands::list_subtasks,
+      commands::reorder_task,
+      commands::archive_task,
+      commands::unarchive_task,
+      commands::bulk_archive_completed,
+      commands::duplicate_task,
+      commands::merge_tasks,
+      commands::snooze_task,
+      commands::move_overdue_to_today,
+      commands::reset_all,
+      history::undo_last,
+      history::redo_last,
+      due::parse_due,
+      stats::task_stats,
+      streak::get_streak,
+      agenda::agenda,
+      settings::get_setting,
+      settings::set_setting,
+      settings::get_settings,
+      lists::create_list,
+      lists::list_lists,
+      lists::move_task,
+      lists::delete_list,
+      attachments::add_attachment,
+      attachments::list_attachments,
+      checklist::add_checklist_item,
+      checklist::toggle_checklist_item,
+      checklist::reorder_checklist_item,
+      tags::add_tag,
+      tags::remove_tag,
+      tags::list_tasks_by_tag,
+      transfer::export_tasks,
+      transfer::import_tasks,
+      transfer::export_tasks_csv,
+      transfer::import_tasks_csv,
+      todoist::import_todoist,
+      views::set_view_state,
+      views::get_view_state
+    ])
+    .build(tauri::generate_context!())
+    .expect("error while building tauri application")
+    .run(|app, event| {
+      if let RunEvent::Exit = event {
+        if let Some(notes) = app.try_state::<Notes>() {
+          tauri::async_runtime::block_on(notes.flush_all(app));
+        }
+        if let Some(reminders) = app.try_state::<Reminders>() {
+          reminders.stop();
+        }
+        if let Some(backups) = app.try_state::<Backups>() {
+          backups.stop();
+        }
+      }
+    });
+}
+
+/// Shows `message` and quits once the user dismisses it.
+fn fatal(app: &AppHandle, message: String) {
+  let handle = app.clone();
+  app
+    .dialog()
+    .message(message)
+    .title("Tasks")
+    .kind(MessageDialogKind::Error)
+    .show(move |_| handle.exit(1));
+}
@@ -0,0 +1,383 @@
+use chrono::Utc;
+use sqlx::error::BoxDynError;
+use sqlx::migrate::{Migration as SqlxMigration, MigrationSource, MigrationType, Migrator};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteConnection};
+use sqlx::ConnectOptions;
+use std::future::Future;
+use std::pin::Pin;
+use tauri_plugin_sql::{Migration, MigrationKind};
+
+use crate::models::timestamp;
+
+pub fn migrations() -> Vec<Migration> {
+  vec![
+    // `IF NOT EXISTS`: databases from earlier builds already have a tasks
+    // table created by the frontend (brought up to this shape first by
+    // `repair_legacy_tasks`).
+    Migration {
+      version: 1,
+      description: "create tasks",
+      sql: "CREATE TABLE IF NOT EXISTS tasks (
+              id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+              title               TEXT NOT NULL,
+              done                INTEGER NOT NULL DEFAULT 0,
+              list_id             INTEGER REFERENCES lists(id) ON DELETE CASCADE,
+              accumulated_seconds INTEGER NOT NULL DEFAULT 0,
+              running_since       TEXT NULL,
+              created_at          TEXT NULL,
+              due                 TEXT NULL
+            );",
+      kind: MigrationKind::Up,
+    },
+    Migration {
+      version: 2,
+      description: "add task notes",
+      sql: "ALTER TABLE tasks ADD COLUMN notes TEXT NULL;",
+      kind: MigrationKind::Up,
+    },
+    Migration {
+      version: 3,
+      description: "add task soft delete",
+      sql: "ALTER TABLE tasks ADD COLUMN deleted_at TEXT NULL;",
+      kind: MigrationKind::Up,
+    },
+    // External-content FTS index over title/notes, kept in sync by triggers.
+    Migration {
+      version: 4,
+      description: "add task full-text search",
+      sql: "CREATE VIRTUAL TABLE tasks_fts USING fts5(
+              title, notes, content='tasks', content_rowid='id'
+            );
+            INSERT INTO tasks_fts(tasks_fts) VALUES ('rebuild');
+
+            CREATE TRIGGER tasks_fts_ai AFTER INSERT ON tasks BEGIN
+              INSERT INTO tasks_fts(rowid, title, notes) VALUES (new.id, new.title, new.notes);
+            END;
+            CREATE TRIGGER tasks_fts_ad AFTER DELETE ON tasks BEGIN
+              INSERT INTO tasks_fts(tasks_fts, rowid, title, notes)
+              VALUES ('delete', old.id, old.title, old.notes);
+            END;
+            CREATE TRIGGER tasks_fts_au AFTER UPDATE OF title, notes ON tasks BEGIN
+              INSERT INTO tasks_fts(tasks_fts, rowid, title, notes)
+              VALUES ('delete', old.id, old.title, old.notes);
+              INSERT INTO tasks_fts(rowid, title, notes) VALUES (new.id, new.title, new.notes);
+            END;",
+      kind: MigrationKind::Up,
+    },
+    Migration {
+      version: 5,
+      description: "add task repeat rule",
+      sql: "ALTER TABLE tasks ADD COLUMN repeat TEXT NOT NULL DEFAULT 'none';",
+      kind: MigrationKind::Up,
+    },
+    // 0 = low, 1 = medium, 2 = high
+    Migration {
+      version: 6,
+      description: "add task priority",
+      sql: "ALTER TABLE tasks ADD COLUMN priority INTEGER NOT NULL DEFAULT 1;",
+      kind: MigrationKind::Up,
+    },
+    Migration {
+      version: 7,
+      description: "add tags",
+      sql: "CREATE TABLE tags (
+              id   INTEGER PRIMARY KEY AUTOINCREMENT,
+              name TEXT NOT NULL UNIQUE
+            );
+            CREATE TABLE task_tags (
+              task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+              tag_id  INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+              PRIMARY KEY (task_id, tag_id)
+            );
+            CREATE INDEX idx_task_tags_tag ON task_tags(tag_id);",
+      kind: MigrationKind::Up,
+    },
+    // Commands set updated_at themselves (so RETURNING sees it); the triggers
+    // cover writes the frontend still makes through the SQL plugin.
+    Migration {
+      version: 8,
+      description: "add task updated_at",
+      sql: "ALTER TABLE tasks ADD COLUMN updated_at TEXT NULL;
+            UPDATE tasks SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now');
+
+            CREATE TRIGGER tasks_touch_ai AFTER INSERT ON tasks
+            WHEN NEW.updated_at IS NULL BEGIN
+              UPDATE tasks SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+              WHERE id = NEW.id;
+            END;
+            CREATE TRIGGER tasks_touch_au AFTER UPDATE ON tasks
+            WHEN NEW.updated_at IS OLD.updated_at BEGIN
+              UPDATE tasks SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+              WHERE id = NEW.id;
+            END;",
+      kind: MigrationKind::Up,
+    },
+    Migration {
+      version: 9,
+      description: "add task parent",
+      sql: "ALTER TABLE tasks ADD COLUMN parent_id INTEGER NULL
+              REFERENCES tasks(id) ON DELETE SET NULL;
+            CREATE INDEX idx_tasks_parent ON tasks(parent_id);",
+      kind: MigrationKind::Up,
+    },
+    // Completion times before this are unknown; updated_at is the best guess.
+    Migration {
+      version: 10,
+      description: "add task completed_at",
+      sql: "ALTER TABLE tasks ADD COLUMN completed_at TEXT NULL;
+            UPDATE tasks SET completed_at = updated_at WHERE done = 1;
+
+            CREATE TRIGGER tasks_completed_au AFTER UPDATE OF done ON tasks
+            WHEN NEW.done IS NOT OLD.done AND NEW.completed_at IS OLD.completed_at BEGIN
+              UPDATE tasks SET completed_at =
+                CASE WHEN NEW.done THEN strftime('%Y-%m-%dT%H:%M:%fZ', 'now') END
+              WHERE id = NEW.id;
+            END;",
+      kind: MigrationKind::Up,
+    },
+    // Existing tasks keep their creation order. Inserts that leave sort_order
+    // at 0 (the frontend's, imports without one) are put at the end.
+    Migration {
+      version: 11,
+      description: "add task sort_order",
+      sql: "ALTER TABLE tasks ADD COLUMN sort_order REAL NOT NULL DEFAULT 0;
+            UPDATE tasks SET sort_order = id;
+
+            CREATE TRIGGER tasks_order_ai AFTER INSERT ON tasks
+            WHEN NEW.sort_order = 0 BEGIN
+              UPDATE tasks SET sort_order = (SELECT MAX(sort_order) + 1 FROM tasks)
+              WHERE id = NEW.id;
+            END;",
+      kind: MigrationKind::Up,
+    },
+    Migration {
+      version: 12,
+      description: "add task archived",
+      sql: "ALTER TABLE tasks ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;",
+      kind: MigrationKind::Up,
+    },
+    // The frontend (src/db.ts) has always created these on load, and still
+    // repairs older shapes of them; this just makes sure they exist before
+    // any list command runs. Same definitions, so its checks stay no-ops.
+    Migration {
+      version: 13,
+      description: "create spaces, folders and lists",
+      sql: "CREATE TABLE IF NOT EXISTS spaces (
+              id    INTEGER PRIMARY KEY AUTOINCREMENT,
+              name  TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS folders (
+              id        INTEGER PRIMARY KEY AUTOINCREMENT,
+              space_id  INTEGER NOT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+              name      TEXT NOT NULL,
+              position  INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE IF NOT EXISTS lists (
+              id        INTEGER PRIMARY KEY AUTOINCREMENT,
+              space_id  INTEGER NOT NULL REFERENCES spaces(id) ON DELETE CASCADE,
+              folder_id INTEGER NULL REFERENCES folders(id) ON DELETE CASCADE,
+              name      TEXT NOT NULL,
+              position  INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE INDEX IF NOT EXISTS idx_tasks_list ON tasks(list_id);",
+      kind: MigrationKind::Up,
+    },
+    // Rows only; the files themselves are never touched.
+    Migration {
+      version: 14,
+      description: "create attachments",
+      sql: "CREATE TABLE attachments (
+              id          INTEGER PRIMARY KEY AUTOINCREMENT,
+              task_id     INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+              file_name   TEXT NOT NULL,
+              path        TEXT NOT NULL,
+              size        INTEGER NOT NULL,
+              modified_at TEXT NULL,
+              attached_at TEXT NOT NULL
+            );
+            CREATE INDEX idx_attachments_task ON attachments(task_id);",
+      kind: MigrationKind::Up,
+    },
+    // Values are free-form text; known keys are parsed by `get_settings`.
+    Migration {
+      version: 15,
+      description: "create settings",
+      sql: "CREATE TABLE settings (
+              key        TEXT PRIMARY KEY,
+              value      TEXT NOT NULL,
+              updated_at TEXT NOT NULL
+            );",
+      kind: MigrationKind::Up,
+    },
+    // Goes with its task on a hard delete, through the foreign key.
+    Migration {
+      version: 16,
+      description: "create checklist items",
+      sql: "CREATE TABLE checklist_items (
+              id       INTEGER PRIMARY KEY AUTOINCREMENT,
+              task_id  INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+              text     TEXT NOT NULL,
+              done     INTEGER NOT NULL DEFAULT 0,
+              position INTEGER NOT NULL
+            );
+            CREATE INDEX idx_checklist_items_task ON checklist_items(task_id, position);",
+      kind: MigrationKind::Up,
+    },
+    // One row per hard-deleted task, for `tasks_changed_since`; dropped again
+    // if the id comes back (undo). Trashed tasks are only updated, not here.
+    Migration {
+      version: 17,
+      description: "add task tombstones",
+      sql: "CREATE TABLE task_tombstones (
+              task_id    INTEGER PRIMARY KEY,
+              deleted_at TEXT NOT NULL
+            );
+            CREATE INDEX idx_task_tombstones_deleted ON task_tombstones(deleted_at);
+            CREATE INDEX idx_tasks_updated ON tasks(updated_at);
+
+            CREATE TRIGGER tasks_tombstone_ad AFTER DELETE ON tasks BEGIN
+              INSERT OR REPLACE INTO task_tombstones (task_id, deleted_at)
+              VALUES (OLD.id, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'));
+            END;
+            CREATE TRIGGER tasks_tombstone_ai AFTER INSERT ON tasks BEGIN
+              DELETE FROM task_tombstones WHERE task_id = NEW.id;
+            END;",
+      kind: MigrationKind::Up,
+    },
+    // A single row, filled in from past completions the first time it's read.
+    Migration {
+      version: 18,
+      description: "create streaks",
+      sql: "CREATE TABLE streaks (
+              id         INTEGER PRIMARY KEY CHECK (id = 1),
+              current    INTEGER NOT NULL,
+              longest    INTEGER NOT NULL,
+              last_day   TEXT NULL,
+              updated_at TEXT NOT NULL
+            );",
+      kind: MigrationKind::Up,
+    },
+    // `tasks_changed_since` cursor: bumped on every write to a task, so it
+    // orders changes the way they committed, whatever the clock does. The
+    // touch trigger skips the row's own cursor update, or each write would
+    // stamp `updated_at` twice.
+    Migration {
+      version: 19,
+      description: "add task change sequence",
+      sql: "CREATE TABLE change_seq (
+              id    INTEGER PRIMARY KEY CHECK (id = 1),
+              value INTEGER NOT NULL
+            );
+            INSERT INTO change_seq (id, value) VALUES (1, 1);
+            ALTER TABLE tasks ADD COLUMN change_seq INTEGER NOT NULL DEFAULT 1;
+            ALTER TABLE task_tombstones ADD COLUMN change_seq INTEGER NOT NULL DEFAULT 1;
+            CREATE INDEX idx_tasks_change_seq ON tasks(change_seq);
+            CREATE INDEX idx_task_tombstones_change_seq ON task_tombstones(change_seq);
+
+            DROP TRIGGER tasks_touch_au;
+            CREATE TRIGGER tasks_touch_au AFTER UPDATE ON tasks
+            WHEN NEW.updated_at IS OLD.updated_at AND NEW.change_seq IS OLD.change_seq BEGIN
+              UPDATE tasks SET updated_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+              WHERE id = NEW.id;
+            END;
+            CREATE TRIGGER tasks_seq_ai AFTER INSERT ON tasks BEGIN
+              UPDATE change_seq SET value = value + 1;
+              UPDATE tasks SET change_seq = (SELECT value FROM change_seq) WHERE id = NEW.id;
+            END;
+            CREATE TRIGGER tasks_seq_au AFTER UPDATE ON tasks
+            WHEN NEW.change_seq IS OLD.change_seq BEGIN
+              UPDATE change_seq SET value = value + 1;
+              UPDATE tasks SET change_seq = (SELECT value FROM change_seq) WHERE id = NEW.id;
+            END;
+            DROP TRIGGER tasks_tombstone_ad;
+            CREATE TRIGGER tasks_tombstone_ad AFTER DELETE ON tasks BEGIN
+              UPDATE change_seq SET value = value + 1;
+              INSERT OR REPLACE INTO task_tombstones (task_id, deleted_at, change_seq)
+              VALUES (OLD.id, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                      (SELECT value FROM change_seq));
+            END;",
+      kind: MigrationKind::Up,
+    },
+  ]
+}
+
+/// Schema version a fully migrated database is at.
+pub fn latest_version() -> i64 {
+  migrations().iter().map(|m| m.version).max().unwrap_or(0)
+}
+
+/// Applies pending migrations at startup. The SQL plugin can only preload
+/// databases named in `tauri.conf.json`, and ours lives at a per-user path, so
+/// it no longer migrates anything itself (see `AppDb::install`). Earlier builds
+/// had it record progress in the same `_sqlx_migrations` table.
+pub async fn run(options: &SqliteConnectOptions) -> sqlx::Result<()> {
+  let mut conn = options.connect().await?;
+  apply(&mut conn).await
+}
+
+/// `run` on a connection that's already open, e.g. the only one of an
+/// in-memory database.
+pub async fn apply(conn: &mut SqliteConnection) -> sqlx::Result<()> {
+  repair_legacy_tasks(conn).await?;
+  Migrator::new(Pending(migrations()))
+    .await?
+    // not `run`, whose future the compiler can't prove `Send` for every
+    // lifetime of `conn`, which `set_db_key` (via `AppDb::open`) needs
+    .run_direct(conn)
+    .await?;
+  Ok(())
+}
+
+/// Tasks tables from before `created_at`/`due` existed: version 1 leaves an
+/// existing table as it is, and every later one reads both columns. Adds them,
+/// and stamps rows without a creation time (which `Task` can't load) with now.
+async fn repair_legacy_tasks(conn: &mut SqliteConnection) -> sqlx::Result<()> {
+  let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info('tasks');")
+    .fetch_all(&mut *conn)
+    .await?;
+  if columns.is_empty() {
+    return Ok(());
+  }
+  for column in ["created_at", "due"] {
+    if !columns.iter().any(|c| c == column) {
+      sqlx::query(&format!("ALTER TABLE tasks ADD COLUMN {column} TEXT NULL;"))
+        .execute(&mut *conn)
+        .await?;
+    }
+  }
+  sqlx::query("UPDATE tasks SET created_at = ? WHERE created_at IS NULL;")
+    .bind(timestamp(&Utc::now()))
+    .execute(&mut *conn)
+    .await?;
+  Ok(())
+}
+
+#[derive(Debug)]
+struct Pending(Vec<Migration>);
+
+// Mirrors the plugin's own conversion, so checksums match the ones earlier
+// builds recorded through it.
+impl MigrationSource<'static> for Pending {
+  fn resolve(
+    self,
+  ) -> Pin<Box<dyn Future<Output = Result<Vec<SqlxMigration>, BoxDynError>> + Send>> {
+    Box::pin(async move {
+      Ok(
+        self
+          .0
+          .into_iter()
+          .filter(|m| matches!(m.kind, MigrationKind::Up))
+          .map(|m| {
+            SqlxMigration::new(
+              m.version,
+              m.description.into(),
+              MigrationType::ReversibleUp,
+              m.sql.into(),
+              false,
+            )
+          })
+          .collect(),
+      )
+    })
+  }
+}
@@ -0,0 +1,308 @@
+use chrono::{DateTime, Days, Local, Months, SecondsFormat, TimeDelta, TimeZone, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{FromRow, Row};
+
+use crate::checklist::ChecklistItem;
+
+/// Storage format for timestamps: the same shape the frontend writes with
+/// `new Date().toISOString()`, so stored values stay comparable as strings.
+pub(crate) fn timestamp(dt: &DateTime<Utc>) -> String {
+  dt.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Column list matching `Task::from_row`, for use in `SELECT`/`RETURNING`.
+pub const TASK_COLUMNS: &str =
+  "id, title, notes, done, list_id, created_at, updated_at, due, deleted_at, repeat, priority, parent_id, \
+   completed_at, sort_order, archived";
+
+/// Optional extra column (append after `TASK_COLUMNS`) that loads each task's
+/// tag names as a JSON array, sorted. Without it `Task::tags` is left empty.
+pub const TASK_TAGS: &str = "(SELECT json_group_array(name) FROM ( \
+     SELECT tg.name FROM task_tags tt JOIN tags tg ON tg.id = tt.tag_id \
+     WHERE tt.task_id = tasks.id ORDER BY tg.name \
+   )) AS tags";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Task {
+  pub id: i64,
+  pub title: String,
+  pub notes: Option<String>,
+  pub done: bool,
+  pub created_at: DateTime<Utc>,
+  /// Last write; pass back as `expected_updated_at` to detect conflicts.
+  pub updated_at: Option<DateTime<Utc>>,
+  pub due: Option<DateTime<Utc>>,
+  /// Set when the task has been moved to the trash.
+  pub deleted_at: Option<DateTime<Utc>>,
+  pub repeat: Option<RepeatRule>,
+  #[serde(default)]
+  pub priority: Priority,
+  pub parent_id: Option<i64>,
+  pub list_id: Option<i64>,
+  pub completed_at: Option<DateTime<Utc>>,
+  #[serde(default)]
+  pub sort_order: f64,
+  #[serde(default)]
+  pub archived: bool,
+  #[serde(default)]
+  pub tags: Vec<String>,
+  /// Only loaded by `get_task` when asked for; `None` otherwise.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub checklist: Option<Vec<ChecklistItem>>,
+}
+
+/// Result of `toggle_task_done`: the toggled task plus, when completing a
+/// recurring task, its newly spawned next occurrence, and the ids of any
+/// subtasks completed along with it.
+#[derive(Serialize, Clone, Debug)]
+pub struct Toggled {
+  pub task: Task,
+  pub next: Option<Task>,
+  pub completed_subtasks: Vec<i64>,
+}
+
+/// Result of `list_tasks_paged`.
+#[derive(Serialize, Clone, Debug)]
+pub struct PagedTasks {
+  pub items: Vec<Task>,
+  pub total: u64,
+}
+
+impl<'r> FromRow<'r, SqliteRow> for Task {
+  fn from_row(row: &'r SqliteRow) -> sqlx::Result<Self> {
+    Ok(Self {
+      id: row.try_get("id")?,
+      title: row.try_get("title")?,
+      notes: row.try_get("notes")?,
+      done: row.try_get("done")?,
+      created_at: row.try_get("created_at")?,
+      updated_at: row.try_get("updated_at")?,
+      due: row.try_get("due")?,
+      deleted_at: row.try_get("deleted_at")?,
+      repeat: RepeatRule::from_column(row.try_get("repeat")?),
+      priority: Priority::from_column(row.try_get("priority")?),
+      parent_id: row.try_get("parent_id")?,
+      list_id: row.try_get("list_id")?,
+      completed_at: row.try_get("completed_at")?,
+      sort_order: row.try_get("sort_order")?,
+      archived: row.try_get("archived")?,
+      tags: match row.try_get::<Option<String>, _>("tags") {
+        Ok(json) => json
+          .map(|j| serde_json::from_str(&j))
+          .transpose()
+          .map_err(|e| sqlx::Error::Decode(Box::new(e)))?
+          .unwrap_or_default(),
+        Err(sqlx::Error::ColumnNotFound(_)) => Vec::new(),
+        Err(e) => return Err(e),
+      },
+      checklist: None,
+    })
+  }
+}
+
+/// Deserializes a present field as `Some`, so `Option<Option<T>>` can tell
+/// "absent" (`None`) from an explicit `null` (`Some(None)`).
+fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+  D: Deserializer<'de>,
+  T: Deserialize<'de>,
+{
+  T::deserialize(deserializer).map(Some)
+}
+
+/// Fields of a task `create_task` takes; only `title` is required.
+#[derive(Clone, Debug, Default)]
+pub struct NewTask {
+  pub title: String,
+  /// RFC3339.
+  pub due: Option<String>,
+  pub repeat: Option<RepeatRule>,
+  pub priority: Option<Priority>,
+  pub parent_id: Option<i64>,
+  pub list_id: Option<i64>,
+}
+
+/// Fields to change in `update_task`; anything left out is not written.
+/// Nullable fields take `null` to clear them.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct TaskPatch {
+  pub title: Option<String>,
+  #[serde(default, deserialize_with = "present")]
+  pub notes: Option<Option<String>>,
+  pub done: Option<bool>,
+  #[serde(default, deserialize_with = "present")]
+  pub due: Option<Option<String>>,
+  #[serde(default, deserialize_with = "present")]
+  pub repeat: Option<Option<RepeatRule>>,
+  pub priority: Option<Priority>,
+  #[serde(default, deserialize_with = "present")]
+  pub parent_id: Option<Option<i64>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RepeatRule {
+  Daily,
+  Weekly,
+  Monthly,
+}
+
+impl RepeatRule {
+  /// Parses the `repeat` column; `none` (or anything unknown) is no rule.
+  pub(crate) fn from_column(value: &str) -> Option<Self> {
+    match value {
+      "daily" => Some(RepeatRule::Daily),
+      "weekly" => Some(RepeatRule::Weekly),
+      "monthly" => Some(RepeatRule::Monthly),
+      _ => None,
+    }
+  }
+
+  pub(crate) fn to_column(rule: Option<Self>) -> &'static str {
+    match rule {
+      None => "none",
+      Some(RepeatRule::Daily) => "daily",
+      Some(RepeatRule::Weekly) => "weekly",
+      Some(RepeatRule::Monthly) => "monthly",
+    }
+  }
+
+  /// Due date of the occurrence after `due`. Stepped on the local calendar so
+  /// the wall-clock time survives DST; monthly clamps to the last day of a
+  /// shorter month (Jan 31 -> Feb 28).
+  pub(crate) fn next_after(self, due: DateTime<Utc>) -> DateTime<Utc> {
+    let local = due.with_timezone(&Local).naive_local();
+    let next = match self {
+      RepeatRule::Daily => local.checked_add_days(Days::new(1)),
+      RepeatRule::Weekly => local.checked_add_days(Days::new(7)),
+      RepeatRule::Monthly => local.checked_add_months(Months::new(1)),
+    }
+    .unwrap_or(local);
+    Local
+      .from_local_datetime(&next)
+      .earliest()
+      .map(|dt| dt.with_timezone(&Utc))
+      // the wall-clock time falls in a DST gap; keep the same instant offset
+      .unwrap_or_else(|| due + (next - local))
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+  Low,
+  #[default]
+  Medium,
+  High,
+}
+
+impl Priority {
+  /// Decodes the `priority` column. Anything unexpected (a corrupt row, or a
+  /// value from a newer build) reads as `Medium` instead of failing the query.
+  pub(crate) fn from_column(value: i64) -> Self {
+    match value {
+      0 => Priority::Low,
+      2 => Priority::High,
+      _ => Priority::Medium,
+    }
+  }
+
+  pub(crate) fn to_column(self) -> i64 {
+    match self {
+      Priority::Low => 0,
+      Priority::Medium => 1,
+      Priority::High => 2,
+    }
+  }
+
+  pub(crate) fn name(self) -> &'static str {
+    match self {
+      Priority::Low => "low",
+      Priority::Medium => "medium",
+      Priority::High => "high",
+    }
+  }
+
+  pub(crate) fn from_name(name: &str) -> Option<Self> {
+    match name.to_ascii_lowercase().as_str() {
+      "low" => Some(Priority::Low),
+      "medium" => Some(Priority::Medium),
+      "high" => Some(Priority::High),
+      _ => None,
+    }
+  }
+}
+
+/// How far `snooze_task` pushes a due date: `{"minutes": 30}`,
+/// `{"hours": 2}`, `"tomorrow"` or `"next_week"`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SnoozeSpec {
+  Minutes(u32),
+  Hours(u32),
+  Tomorrow,
+  NextWeek,
+}
+
+impl SnoozeSpec {
+  /// `base` pushed back; whole days keep the same local wall-clock time.
+  pub(crate) fn after(self, base: DateTime<Utc>) -> DateTime<Utc> {
+    match self {
+      SnoozeSpec::Minutes(n) => base + TimeDelta::minutes(n.into()),
+      SnoozeSpec::Hours(n) => base + TimeDelta::hours(n.into()),
+      SnoozeSpec::Tomorrow => RepeatRule::Daily.next_after(base),
+      SnoozeSpec::NextWeek => RepeatRule::Weekly.next_after(base),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskFilter {
+  All,
+  Active,
+  Completed,
+  Archived,
+}
+
+impl TaskFilter {
+  /// Value bound against `done`; `None` matches every task.
+  pub(crate) fn done(self) -> Option<bool> {
+    match self {
+      TaskFilter::All | TaskFilter::Archived => None,
+      TaskFilter::Active => Some(false),
+      TaskFilter::Completed => Some(true),
+    }
+  }
+
+  /// Value bound against `archived`: archived tasks only show up under
+  /// their own filter.
+  pub(crate) fn archived(self) -> bool {
+    self == TaskFilter::Archived
+  }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+  CreatedAsc,
+  CreatedDesc,
+  DueAsc,
+  PriorityDesc,
+  ManualOrder,
+}
+
+impl SortBy {
+  /// `ORDER BY` clause; a fixed string per variant, never built from input.
+  pub(crate) fn order_by(self) -> &'static str {
+    match self {
+      SortBy::CreatedAsc => "created_at ASC, id ASC",
+      SortBy::CreatedDesc => "created_at DESC, id DESC",
+      // undated tasks go last rather than sorting first as NULL
+      SortBy::DueAsc => "due IS NULL, due ASC, id ASC",
+      SortBy::PriorityDesc => "priority DESC, due IS NULL, due ASC, id ASC",
+      SortBy::ManualOrder => "sort_order ASC, id ASC",
+    }
+  }
+}
@@ -0,0 +1,168 @@
+use chrono::Utc;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::async_runtime;
+use tauri::{AppHandle, Manager, State};
+
+use crate::db::{with_retry, AppDb};
+use crate::error::{guard_sync, AppError};
+use crate::events::{self, ChangeKind};
+use crate::history::{History, Recorder};
+use crate::models::timestamp;
+
+/// How long `save_notes` waits for the next keystroke before writing.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+struct Pending {
+  notes: Option<String>,
+  /// Which `save_notes` call this is, so an older call's timer doesn't write
+  /// a newer value early.
+  generation: u64,
+}
+
+#[derive(Default)]
+struct Queue {
+  generation: u64,
+  pending: HashMap<i64, Pending>,
+}
+
+/// Notes passed to `save_notes` and not written yet, per task. Managed state.
+#[derive(Default)]
+pub struct Notes {
+  queue: Arc<Mutex<Queue>>,
+  /// Held for the whole of a write, so `flush` also waits out one a timer has
+  /// already taken off the queue.
+  writing: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl Notes {
+  /// Writes `id`'s pending notes now, if it has any.
+  pub(crate) async fn flush(&self, app: &AppHandle, id: i64) -> Result<(), AppError> {
+    let _writing = self.writing.lock().await;
+    let pending = self.queue.lock().unwrap().pending.remove(&id);
+    match pending {
+      Some(pending) => write(app, id, pending.notes).await,
+      None => Ok(()),
+    }
+  }
+
+  /// Drops `id`'s pending notes, for a write that sets them itself. Waits out
+  /// one already under way, so it can't land afterwards.
+  pub(crate) async fn discard(&self, id: i64) {
+    let _writing = self.writing.lock().await;
+    self.queue.lock().unwrap().pending.remove(&id);
+  }
+
+  /// Drops everything still pending, once the tasks it was for are gone. Waits
+  /// out a write already under way, so nothing lands afterwards.
+  pub(crate) async fn discard_all(&self) {
+    let _writing = self.writing.lock().await;
+    self.queue.lock().unwrap().pending.clear();
+  }
+
+  /// Writes everything still pending, so quitting mid-sentence loses nothing.
+  pub async fn flush_all(&self, app: &AppHandle) {
+    let _writing = self.writing.lock().await;
+    let pending = std::mem::take(&mut self.queue.lock().unwrap().pending);
+    for (id, pending) in pending {
+      if let Err(e) = write(app, id, pending.notes).await {
+        log::error!("could not save notes of task {id}: {e}");
+      }
+    }
+  }
+
+  /// What `save_notes` does, with the write passed in: queues `notes` for
+  /// `id` and, once calls for it stop for `DEBOUNCE`, hands the latest value
+  /// to `write`. Runs without an app (integration tests).
+  pub fn save<W, F>(&self, id: i64, notes: Option<String>, write: W)
+  where
+    W: FnOnce(Option<String>) -> F + Send + 'static,
+    F: Future<Output = Result<(), AppError>> + Send,
+  {
+    let generation = {
+      let mut queue = self.queue.lock().unwrap();
+      queue.generation += 1;
+      let generation = queue.generation;
+      queue.pending.insert(id, Pending { notes, generation });
+      generation
+    };
+
+    let queue = Arc::clone(&self.queue);
+    let writing = Arc::clone(&self.writing);
+    async_runtime::spawn(async move {
+      tokio::time::sleep(DEBOUNCE).await;
+      let _writing = writing.lock().await;
+      let pending = {
+        let mut queue = queue.lock().unwrap();
+        match queue.pending.get(&id) {
+          Some(pending) if pending.generation == generation => queue.pending.remove(&id),
+          // a later call is waiting its turn, or `flush` got here first
+          _ => None,
+        }
+      };
+      if let Some(pending) = pending {
+        if let Err(e) = write(pending.notes).await {
+          log::error!("could not save notes of task {id}: {e}");
+        }
+      }
+    });
+  }
+}
+
+async fn write(app: &AppHandle, id: i64, notes: Option<String>) -> Result<(), AppError> {
+  write_notes(&app.state::<AppDb>(), &app.state::<History>(), id, notes).await?;
+  events::task_changed(app, id, ChangeKind::Updated);
+  Ok(())
+}
+
+/// The write behind `save_notes`, minus the change event; takes the pool
+/// directly, so it runs without an app (integration tests).
+pub async fn write_notes(
+  db: &AppDb,
+  history: &History,
+  id: i64,
+  notes: Option<String>,
+) -> Result<(), AppError> {
+  let now = timestamp(&Utc::now());
+  let entry = with_retry("save_notes", || async {
+    let mut tx = db.pool().begin().await?;
+    let undo = Recorder::start(&mut tx, "save_notes", &[id]).await?;
+    let result = sqlx::query("UPDATE tasks SET notes = ?, updated_at = ? WHERE id = ?;")
+      .bind(&notes)
+      .bind(&now)
+      .bind(id)
+      .execute(&mut *tx)
+      .await?;
+    if result.rows_affected() == 0 {
+      return Err(AppError::NotFound.into());
+    }
+    let entry = undo.finish(&mut tx).await?;
+    tx.commit().await?;
+    Ok(entry)
+  })
+  .await?;
+  history.record(entry);
+  Ok(())
+}
+
+/// Sets a task's notes (`None` clears them), for calling on every keystroke:
+/// the write happens once calls for the task stop for `DEBOUNCE`, with the
+/// latest value. `get_task` writes a pending value first, so it never returns
+/// stale notes, and `update_task` drops it; failures past this point are only
+/// logged.
+#[tauri::command]
+pub fn save_notes(
+  app: AppHandle,
+  queue: State<'_, Notes>,
+  id: i64,
+  notes: Option<String>,
+) -> Result<(), AppError> {
+  guard_sync("save_notes", || {
+    queue.save(id, notes, move |notes| async move {
+      write(&app, id, notes).await
+    });
+    Ok(())
+  })
+}
@@ -0,0 +1,81 @@
+use chrono::{Duration as ChronoDuration, Local, Utc};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::async_runtime::{self, JoinHandle};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::db::AppDb;
+use crate::models::{timestamp, Task, TASK_COLUMNS};
+
+const INTERVAL: Duration = Duration::from_secs(60);
+const LOOKAHEAD_MINUTES: i64 = 5;
+
+/// Ids already announced while they sit inside the lookahead window.
+#[derive(Default)]
+pub struct Notified(pub Mutex<HashSet<i64>>);
+
+/// Handle to the reminder loop; `stop` it when the app exits.
+pub struct Reminders(JoinHandle<()>);
+
+impl Reminders {
+  pub fn stop(&self) {
+    self.0.abort();
+  }
+}
+
+pub fn spawn(app: &AppHandle) -> Reminders {
+  let app = app.clone();
+  Reminders(async_runtime::spawn(async move {
+    let mut ticker = tokio::time::interval(INTERVAL);
+    loop {
+      ticker.tick().await;
+      if let Err(e) = check(&app).await {
+        log::error!("reminder check failed: {e}");
+      }
+    }
+  }))
+}
+
+async fn check(app: &AppHandle) -> sqlx::Result<()> {
+  let now = Utc::now();
+  let upcoming = sqlx::query_as::<_, Task>(&format!(
+    "SELECT {TASK_COLUMNS} FROM tasks \
+     WHERE done = 0 AND deleted_at IS NULL AND due > ? AND due <= ?;"
+  ))
+  .bind(timestamp(&now))
+  .bind(timestamp(
+    &(now + ChronoDuration::minutes(LOOKAHEAD_MINUTES)),
+  ))
+  .fetch_all(&app.state::<AppDb>().pool())
+  .await?;
+
+  let fresh: Vec<Task> = {
+    let notified = app.state::<Notified>();
+    let mut notified = notified.0.lock().unwrap_or_else(|e| e.into_inner());
+    // forget tasks that left the window (done, past due, or snoozed), so a
+    // task that comes back into range is announced again
+    notified.retain(|id| upcoming.iter().any(|t| t.id == *id));
+    upcoming
+      .into_iter()
+      .filter(|t| notified.insert(t.id))
+      .collect()
+  };
+
+  for task in fresh {
+    let at = task
+      .due
+      .map(|d| d.with_timezone(&Local).format("%H:%M").to_string());
+    let result = app
+      .notification()
+      .builder()
+      .title(&task.title)
+      .body(format!("Due at {}", at.unwrap_or_default()))
+      .show();
+    if let Err(e) = result {
+      log::warn!("could not show reminder for task {}: {e}", task.id);
+    }
+  }
+  Ok(())
+}
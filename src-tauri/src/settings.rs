@@ -0,0 +1,135 @@
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tauri::State;
+
+use crate::db::{with_retry, AppDb};
+use crate::error::{guard, AppError};
+use crate::logging::log_error;
+use crate::models::{timestamp, SortBy, TaskFilter};
+
+const THEME: &str = "theme";
+const DEFAULT_SORT: &str = "default_sort";
+const DEFAULT_FILTER: &str = "default_filter";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+  /// Follow the OS.
+  System,
+  Light,
+  Dark,
+}
+
+/// The settings the app knows about, with defaults for any not yet set.
+#[derive(Serialize, Clone, Debug)]
+pub struct Settings {
+  pub theme: Theme,
+  pub default_sort: SortBy,
+  pub default_filter: TaskFilter,
+}
+
+impl Default for Settings {
+  fn default() -> Self {
+    Self {
+      theme: Theme::System,
+      default_sort: SortBy::ManualOrder,
+      default_filter: TaskFilter::Active,
+    }
+  }
+}
+
+/// A stored value as `T`: JSON, or failing that a bare string, so both
+/// `"dark"` and `dark` read as `Theme::Dark`.
+fn parse<T: DeserializeOwned>(raw: &str) -> serde_json::Result<T> {
+  serde_json::from_str(raw)
+    .or_else(|e| serde_json::from_value(serde_json::Value::String(raw.to_string())).map_err(|_| e))
+}
+
+/// Rejects a value `get_settings` couldn't read back; other keys take anything.
+fn check(key: &str, value: &str) -> Result<(), AppError> {
+  let parsed = match key {
+    THEME => parse::<Theme>(value).map(drop),
+    DEFAULT_SORT => parse::<SortBy>(value).map(drop),
+    DEFAULT_FILTER => parse::<TaskFilter>(value).map(drop),
+    _ => Ok(()),
+  };
+  parsed.map_err(|e| AppError::Validation(format!("invalid value for {key}: {e}")))
+}
+
+/// `key` from `values`, or `default` if it's missing or unreadable.
+fn field<T: DeserializeOwned>(values: &HashMap<String, String>, key: &str, default: T) -> T {
+  let Some(raw) = values.get(key) else {
+    return default;
+  };
+  parse(raw).unwrap_or_else(|e| {
+    log::warn!("ignoring setting {key} = {raw:?}: {e}");
+    default
+  })
+}
+
+/// The raw value stored under `key`, if any.
+#[tauri::command]
+pub async fn get_setting(db: State<'_, AppDb>, key: String) -> Result<Option<String>, AppError> {
+  guard("get_setting", async {
+    sqlx::query_scalar("SELECT value FROM settings WHERE key = ?;")
+      .bind(key.trim())
+      .fetch_optional(&db.pool())
+      .await
+      .map_err(log_error("get_setting"))
+  })
+  .await
+}
+
+/// Stores `value` under `key`, replacing what was there. Keys this build
+/// doesn't know are kept as they are, for newer builds that do.
+#[tauri::command]
+pub async fn set_setting(db: State<'_, AppDb>, key: String, value: String) -> Result<(), AppError> {
+  guard("set_setting", async {
+    let key = key.trim();
+    if key.is_empty() {
+      return Err(AppError::validation("setting key must not be empty"));
+    }
+    check(key, &value)?;
+
+    let now = timestamp(&Utc::now());
+    with_retry("set_setting", || async {
+      sqlx::query(
+        "INSERT INTO settings (key, value, updated_at) VALUES (?, ?, ?) \
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at;",
+      )
+      .bind(key)
+      .bind(&value)
+      .bind(&now)
+      .execute(&db.pool())
+      .await?;
+      Ok(())
+    })
+    .await
+  })
+  .await
+}
+
+/// Every known setting, typed. A missing or malformed value falls back to its
+/// default (logged), rather than failing the whole call.
+#[tauri::command]
+pub async fn get_settings(db: State<'_, AppDb>) -> Result<Settings, AppError> {
+  guard("get_settings", async {
+    let values: HashMap<String, String> =
+      sqlx::query_as::<_, (String, String)>("SELECT key, value FROM settings;")
+        .fetch_all(&db.pool())
+        .await
+        .map_err(log_error("get_settings"))?
+        .into_iter()
+        .collect();
+
+    let defaults = Settings::default();
+    Ok(Settings {
+      theme: field(&values, THEME, defaults.theme),
+      default_sort: field(&values, DEFAULT_SORT, defaults.default_sort),
+      default_filter: field(&values, DEFAULT_FILTER, defaults.default_filter),
+    })
+  })
+  .await
+}
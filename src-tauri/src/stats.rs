@@ -0,0 +1,104 @@
+use chrono::{DateTime, Days, Local, NaiveDate, TimeDelta, TimeZone, Utc};
+use serde::Serialize;
+use tauri::State;
+
+use crate::db::AppDb;
+use crate::error::{guard, AppError};
+use crate::logging::log_error;
+use crate::models::timestamp;
+
+/// Days covered by `Stats::completions`, today included.
+const HISTORY_DAYS: u64 = 14;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Stats {
+  pub total: i64,
+  pub active: i64,
+  pub completed: i64,
+  pub overdue: i64,
+  pub due_today: i64,
+  /// One entry per local day, oldest first, including days with none.
+  pub completions: Vec<DayCount>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DayCount {
+  pub date: NaiveDate,
+  pub completed: i64,
+}
+
+/// Start of `date` in the local timezone.
+pub(crate) fn local_midnight(date: NaiveDate) -> DateTime<Utc> {
+  let midnight = date.and_hms_opt(0, 0, 0).expect("midnight is a valid time");
+  Local
+    .from_local_datetime(&midnight)
+    .earliest()
+    // midnight falls in a DST gap, so the day starts an hour later
+    .or_else(|| {
+      Local
+        .from_local_datetime(&(midnight + TimeDelta::hours(1)))
+        .earliest()
+    })
+    .map(|dt| dt.with_timezone(&Utc))
+    .unwrap_or_else(|| Utc.from_utc_datetime(&midnight))
+}
+
+/// Counts for the dashboard, over tasks not in the trash. "Overdue" and "due
+/// today" only count open tasks; day boundaries follow the local timezone.
+#[tauri::command]
+pub async fn task_stats(db: State<'_, AppDb>) -> Result<Stats, AppError> {
+  guard("task_stats", async {
+    let now = Utc::now();
+    let today = now.with_timezone(&Local).date_naive();
+    let tomorrow = today + Days::new(1);
+    let first = today - Days::new(HISTORY_DAYS - 1);
+
+    let (total, active, completed, overdue, due_today): (i64, i64, i64, i64, i64) = sqlx::query_as(
+      "SELECT COUNT(*), \
+           COALESCE(SUM(done = 0), 0), \
+           COALESCE(SUM(done = 1), 0), \
+           COALESCE(SUM(done = 0 AND due < ?1), 0), \
+           COALESCE(SUM(done = 0 AND due >= ?2 AND due < ?3), 0) \
+         FROM tasks WHERE deleted_at IS NULL;",
+    )
+    .bind(timestamp(&now))
+    .bind(timestamp(&local_midnight(today)))
+    .bind(timestamp(&local_midnight(tomorrow)))
+    .fetch_one(&db.pool())
+    .await
+    .map_err(log_error("task_stats"))?;
+
+    // bucketed here rather than with SQLite's 'localtime', so it agrees with
+    // the boundaries above
+    let done_at: Vec<DateTime<Utc>> = sqlx::query_scalar(
+      "SELECT completed_at FROM tasks \
+       WHERE deleted_at IS NULL AND done = 1 AND completed_at >= ?;",
+    )
+    .bind(timestamp(&local_midnight(first)))
+    .fetch_all(&db.pool())
+    .await
+    .map_err(log_error("task_stats"))?;
+
+    let mut completions: Vec<DayCount> = first
+      .iter_days()
+      .take(HISTORY_DAYS as usize)
+      .map(|date| DayCount { date, completed: 0 })
+      .collect();
+    for at in done_at {
+      let day = at.with_timezone(&Local).date_naive();
+      if let Some(entry) = completions.iter_mut().find(|c| c.date == day) {
+        entry.completed += 1;
+      }
+    }
+
+    Ok(Stats {
+      total,
+      active,
+      completed,
+      overdue,
+      due_today,
+      completions,
+    })
+  })
+  .await
+}
@@ -0,0 +1,122 @@
+use chrono::{DateTime, Days, Local, NaiveDate, Utc};
+use serde::Serialize;
+use sqlx::{FromRow, SqliteConnection};
+use tauri::State;
+
+use crate::db::{with_retry, AppDb};
+use crate::error::{guard, AppError};
+use crate::logging::log_error;
+use crate::models::timestamp;
+
+/// Consecutive local days with at least one task completed.
+#[derive(Serialize, FromRow, Clone, Debug, Default)]
+pub struct Streak {
+  /// Days up to and including `last_day`; 0 once a whole day has passed
+  /// without a completion.
+  pub current: i64,
+  pub longest: i64,
+  /// Most recent day with a completion.
+  pub last_day: Option<NaiveDate>,
+}
+
+async fn stored(conn: &mut SqliteConnection) -> sqlx::Result<Option<Streak>> {
+  sqlx::query_as::<_, Streak>("SELECT current, longest, last_day FROM streaks WHERE id = 1;")
+    .fetch_optional(&mut *conn)
+    .await
+}
+
+async fn save(conn: &mut SqliteConnection, streak: &Streak) -> sqlx::Result<()> {
+  sqlx::query(
+    "INSERT INTO streaks (id, current, longest, last_day, updated_at) VALUES (1, ?, ?, ?, ?) \
+     ON CONFLICT(id) DO UPDATE SET current = excluded.current, longest = excluded.longest, \
+       last_day = excluded.last_day, updated_at = excluded.updated_at;",
+  )
+  .bind(streak.current)
+  .bind(streak.longest)
+  .bind(streak.last_day)
+  .bind(timestamp(&Utc::now()))
+  .execute(&mut *conn)
+  .await?;
+  Ok(())
+}
+
+/// Works the streak out from every completion on record, for the first time
+/// it's needed (the table starts out empty).
+async fn rebuild(conn: &mut SqliteConnection) -> sqlx::Result<Streak> {
+  let done_at: Vec<DateTime<Utc>> = sqlx::query_scalar(
+    "SELECT completed_at FROM tasks WHERE done = 1 AND completed_at IS NOT NULL;",
+  )
+  .fetch_all(&mut *conn)
+  .await?;
+  let mut days: Vec<NaiveDate> = done_at
+    .iter()
+    .map(|at| at.with_timezone(&Local).date_naive())
+    .collect();
+  days.sort();
+  days.dedup();
+
+  let mut streak = Streak::default();
+  for day in days {
+    streak.current = match streak.last_day {
+      Some(last) if last + Days::new(1) == day => streak.current + 1,
+      _ => 1,
+    };
+    streak.longest = streak.longest.max(streak.current);
+    streak.last_day = Some(day);
+  }
+  save(conn, &streak).await?;
+  Ok(streak)
+}
+
+/// Counts a completion at `at` towards the streak. Call inside the
+/// transaction that completes the task; undoing it later doesn't take the day
+/// back.
+pub(crate) async fn record(conn: &mut SqliteConnection, at: DateTime<Utc>) -> sqlx::Result<()> {
+  let Some(mut streak) = stored(conn).await? else {
+    // the completion being recorded is already in the table
+    rebuild(conn).await?;
+    return Ok(());
+  };
+  let day = at.with_timezone(&Local).date_naive();
+  streak.current = match streak.last_day {
+    // already counted (or the clock went back)
+    Some(last) if last >= day => return Ok(()),
+    Some(last) if last + Days::new(1) == day => streak.current + 1,
+    _ => 1,
+  };
+  streak.longest = streak.longest.max(streak.current);
+  streak.last_day = Some(day);
+  save(conn, &streak).await
+}
+
+/// The completion streak, as kept up to date by completing tasks. Days are
+/// local; the current streak survives until the end of the day after its last
+/// completion.
+#[tauri::command]
+pub async fn get_streak(db: State<'_, AppDb>) -> Result<Streak, AppError> {
+  guard("get_streak", async {
+    let mut conn = db.pool().acquire().await.map_err(log_error("get_streak"))?;
+    let streak = stored(&mut conn).await.map_err(log_error("get_streak"))?;
+    drop(conn);
+    let mut streak = match streak {
+      Some(streak) => streak,
+      None => {
+        with_retry("get_streak", || async {
+          let mut tx = db.pool().begin().await?;
+          let streak = rebuild(&mut tx).await?;
+          tx.commit().await?;
+          Ok(streak)
+        })
+        .await?
+      }
+    };
+
+    let today = Local::now().date_naive();
+    if streak.last_day < today.checked_sub_days(Days::new(1)) {
+      // yesterday went by without a completion
+      streak.current = 0;
+    }
+    Ok(streak)
+  })
+  .await
+}
@@ -0,0 +1,63 @@
+use serde::Serialize;
+use tauri::State;
+
+use crate::db::AppDb;
+use crate::error::{guard, AppError};
+use crate::logging::log_error;
+use crate::models::{Task, TASK_COLUMNS, TASK_TAGS};
+
+/// Result of `tasks_changed_since`.
+#[derive(Serialize, Clone, Debug)]
+pub struct ChangeSet {
+  /// Tasks written since then, oldest write first. Moving a task to the trash
+  /// is a write, so trashed tasks show up here with `deleted_at` set.
+  pub tasks: Vec<Task>,
+  /// Ids of tasks deleted for good since then.
+  pub deleted: Vec<i64>,
+  /// The last change these include; pass it back as the next `since`.
+  pub cursor: i64,
+}
+
+/// Everything that changed after the cursor `since` (0 for everything), for
+/// incremental sync. The cursor counts writes rather than reading a clock, so
+/// a write that commits late, or a clock that steps back, can't be skipped.
+/// Both lists are read in one transaction, so they agree with each other and
+/// with `cursor`.
+#[tauri::command]
+pub async fn tasks_changed_since(db: State<'_, AppDb>, since: i64) -> Result<ChangeSet, AppError> {
+  guard("tasks_changed_since", async {
+    changed_since(&db, since).await
+  })
+  .await
+}
+
+/// What `tasks_changed_since` returns; takes the pool directly, so it runs
+/// without an app (integration tests).
+pub async fn changed_since(db: &AppDb, since: i64) -> Result<ChangeSet, AppError> {
+  let read = async {
+    let mut tx = db.pool().begin().await?;
+    let tasks = sqlx::query_as::<_, Task>(&format!(
+      "SELECT {TASK_COLUMNS}, {TASK_TAGS} FROM tasks \
+       WHERE change_seq > ? ORDER BY change_seq, id;"
+    ))
+    .bind(since)
+    .fetch_all(&mut *tx)
+    .await?;
+    let deleted = sqlx::query_scalar(
+      "SELECT task_id FROM task_tombstones WHERE change_seq > ? ORDER BY change_seq, task_id;",
+    )
+    .bind(since)
+    .fetch_all(&mut *tx)
+    .await?;
+    let cursor = sqlx::query_scalar("SELECT value FROM change_seq;")
+      .fetch_one(&mut *tx)
+      .await?;
+    tx.commit().await?;
+    Ok::<_, sqlx::Error>(ChangeSet {
+      tasks,
+      deleted,
+      cursor,
+    })
+  };
+  read.await.map_err(log_error("tasks_changed_since"))
+}
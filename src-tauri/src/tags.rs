@@ -0,0 +1,160 @@
+use chrono::Utc;
+use sqlx::SqliteConnection;
+use tauri::{AppHandle, State};
+
+use crate::db::{with_retry, AppDb};
+use crate::error::{guard, AppError};
+use crate::events::{self, ChangeKind};
+use crate::history::{History, Recorder};
+use crate::logging::log_error;
+use crate::models::{timestamp, Task, TASK_COLUMNS, TASK_TAGS};
+
+/// Tags are stored trimmed and lowercased, so "Work" and " work" are one tag.
+pub(crate) fn normalize(name: &str) -> Result<String, AppError> {
+  let name = name.trim().to_lowercase();
+  if name.is_empty() {
+    return Err(AppError::validation("tag name must not be empty"));
+  }
+  Ok(name)
+}
+
+/// Links `task_id` to the tag `name` (already normalized), creating the tag
+/// on first use.
+pub(crate) async fn attach(
+  conn: &mut SqliteConnection,
+  task_id: i64,
+  name: &str,
+) -> sqlx::Result<()> {
+  let tag_id: i64 = sqlx::query_scalar(
+    "INSERT INTO tags (name) VALUES (?) \
+     ON CONFLICT(name) DO UPDATE SET name = excluded.name \
+     RETURNING id;",
+  )
+  .bind(name)
+  .fetch_one(&mut *conn)
+  .await?;
+  sqlx::query("INSERT OR IGNORE INTO task_tags (task_id, tag_id) VALUES (?, ?);")
+    .bind(task_id)
+    .bind(tag_id)
+    .execute(&mut *conn)
+    .await?;
+  Ok(())
+}
+
+/// Tag edits count as edits of the task itself.
+async fn touch(conn: &mut SqliteConnection, task_id: i64) -> sqlx::Result<()> {
+  sqlx::query("UPDATE tasks SET updated_at = ? WHERE id = ?;")
+    .bind(timestamp(&Utc::now()))
+    .bind(task_id)
+    .execute(&mut *conn)
+    .await?;
+  Ok(())
+}
+
+async fn tags_of(conn: &mut SqliteConnection, task_id: i64) -> sqlx::Result<Vec<String>> {
+  sqlx::query_scalar(
+    "SELECT tg.name FROM task_tags tt JOIN tags tg ON tg.id = tt.tag_id \
+     WHERE tt.task_id = ? ORDER BY tg.name;",
+  )
+  .bind(task_id)
+  .fetch_all(&mut *conn)
+  .await
+}
+
+/// Tags a task; returns its full, updated tag list.
+#[tauri::command]
+pub async fn add_tag(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  task_id: i64,
+  name: String,
+) -> Result<Vec<String>, AppError> {
+  guard("add_tag", async {
+    let name = normalize(&name)?;
+
+    let (tags, entry) = with_retry("add_tag", || async {
+      let mut tx = db.pool().begin().await?;
+      let exists: Option<i64> = sqlx::query_scalar("SELECT id FROM tasks WHERE id = ?;")
+        .bind(task_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+      if exists.is_none() {
+        return Err(AppError::NotFound.into());
+      }
+      let undo = Recorder::start(&mut tx, "add_tag", &[task_id]).await?;
+      attach(&mut tx, task_id, &name).await?;
+      touch(&mut tx, task_id).await?;
+      let tags = tags_of(&mut tx, task_id).await?;
+      let entry = undo.finish(&mut tx).await?;
+      tx.commit().await?;
+      Ok((tags, entry))
+    })
+    .await?;
+    history.record(entry);
+
+    events::task_changed(&app, task_id, ChangeKind::Updated);
+    Ok(tags)
+  })
+  .await
+}
+
+/// Untags a task; tags no task uses any more are dropped.
+#[tauri::command]
+pub async fn remove_tag(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  task_id: i64,
+  name: String,
+) -> Result<Vec<String>, AppError> {
+  guard("remove_tag", async {
+    let name = normalize(&name)?;
+
+    let (tags, entry) = with_retry("remove_tag", || async {
+      let mut tx = db.pool().begin().await?;
+      let undo = Recorder::start(&mut tx, "remove_tag", &[task_id]).await?;
+      sqlx::query(
+        "DELETE FROM task_tags \
+         WHERE task_id = ? AND tag_id = (SELECT id FROM tags WHERE name = ?);",
+      )
+      .bind(task_id)
+      .bind(&name)
+      .execute(&mut *tx)
+      .await?;
+      sqlx::query("DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM task_tags);")
+        .execute(&mut *tx)
+        .await?;
+      touch(&mut tx, task_id).await?;
+      let tags = tags_of(&mut tx, task_id).await?;
+      let entry = undo.finish(&mut tx).await?;
+      tx.commit().await?;
+      Ok((tags, entry))
+    })
+    .await?;
+    history.record(entry);
+
+    events::task_changed(&app, task_id, ChangeKind::Updated);
+    Ok(tags)
+  })
+  .await
+}
+
+#[tauri::command]
+pub async fn list_tasks_by_tag(db: State<'_, AppDb>, name: String) -> Result<Vec<Task>, AppError> {
+  guard("list_tasks_by_tag", async {
+    let name = normalize(&name)?;
+    sqlx::query_as::<_, Task>(&format!(
+      "SELECT {TASK_COLUMNS}, {TASK_TAGS} FROM tasks \
+       WHERE deleted_at IS NULL AND id IN ( \
+         SELECT tt.task_id FROM task_tags tt JOIN tags tg ON tg.id = tt.tag_id WHERE tg.name = ? \
+       ) \
+       ORDER BY created_at DESC, id DESC;"
+    ))
+    .bind(name)
+    .fetch_all(&db.pool())
+    .await
+    .map_err(log_error("list_tasks_by_tag"))
+  })
+  .await
+}
@@ -0,0 +1,221 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use tauri::{AppHandle, State};
+
+use crate::db::{is_busy, with_retry, AppDb, TxError};
+use crate::due;
+use crate::error::{guard, AppError};
+use crate::events::{self, ChangeKind};
+use crate::history::History;
+use crate::lists::default_space;
+use crate::models::{timestamp, Priority};
+
+/// Name of the list `import_todoist` puts everything in.
+const IMPORT_LIST: &str = "Todoist Import";
+
+/// Either a full Sync API backup (`{"items": [...], ...}`) or a plain array
+/// of tasks as the REST API returns them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TodoistExport {
+  Backup { items: Vec<Value> },
+  Tasks(Vec<Value>),
+}
+
+/// The fields we take from a Todoist task; everything else is ignored.
+#[derive(Deserialize)]
+struct TodoistItem {
+  content: String,
+  #[serde(default)]
+  description: Option<String>,
+  #[serde(default)]
+  due: Option<TodoistDue>,
+  #[serde(default)]
+  priority: Option<i64>,
+  /// `checked` in backups, `is_completed` from the REST API; older backups
+  /// use 0/1 for both flags.
+  #[serde(default, deserialize_with = "flag")]
+  checked: bool,
+  #[serde(default, deserialize_with = "flag")]
+  is_completed: bool,
+  #[serde(default, deserialize_with = "flag")]
+  is_deleted: bool,
+}
+
+#[derive(Deserialize)]
+struct TodoistDue {
+  date: String,
+}
+
+fn flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+  Ok(match Value::deserialize(deserializer)? {
+    Value::Bool(b) => b,
+    Value::Number(n) => n.as_i64() != Some(0),
+    _ => false,
+  })
+}
+
+/// A Todoist item that could not be imported, by 0-based position in the
+/// file.
+#[derive(Serialize, Clone, Debug)]
+pub struct ItemError {
+  pub index: usize,
+  pub message: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ImportReport {
+  pub imported: usize,
+  /// Completed, deleted or untitled items, left out on purpose.
+  pub skipped: usize,
+  pub failed: usize,
+  pub errors: Vec<ItemError>,
+}
+
+/// A task ready to insert.
+struct Imported {
+  index: usize,
+  title: String,
+  notes: Option<String>,
+  due: Option<DateTime<Utc>>,
+  priority: Priority,
+}
+
+/// `due.date` is a day ("2024-05-01"), a local time ("2024-05-01T10:00:00")
+/// or a fixed time ("2024-05-01T10:00:00Z"); a bare day is due at its end,
+/// as with `parse_due`.
+fn parse_due(date: &str) -> Result<DateTime<Utc>, String> {
+  let date = date.trim();
+  if let Ok(day) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+    return due::end_of_day(day).ok_or_else(|| format!("invalid due date {date:?}"));
+  }
+  if let Ok(at) = NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S") {
+    return due::local(at).ok_or_else(|| format!("invalid due date {date:?}"));
+  }
+  DateTime::parse_from_rfc3339(date)
+    .map(|dt| dt.with_timezone(&Utc))
+    .map_err(|_| format!("invalid due date {date:?}"))
+}
+
+/// Todoist's priorities run from 1 (none) to 4 (urgent); we only have three.
+fn priority(value: Option<i64>) -> Priority {
+  match value {
+    Some(2) => Priority::Medium,
+    Some(3 | 4) => Priority::High,
+    _ => Priority::Low,
+  }
+}
+
+/// Imports the open tasks of a Todoist JSON export into a new list named
+/// "Todoist Import". Items that can't be read or written are reported back
+/// and the rest still go in.
+#[tauri::command]
+pub async fn import_todoist(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  json: String,
+) -> Result<ImportReport, AppError> {
+  guard("import_todoist", async {
+    let export: TodoistExport = serde_json::from_str(&json)
+      .map_err(|e| AppError::Validation(format!("not a Todoist export: {e}")))?;
+    let items = match export {
+      TodoistExport::Backup { items } | TodoistExport::Tasks(items) => items,
+    };
+
+    let mut report = ImportReport {
+      imported: 0,
+      skipped: 0,
+      failed: 0,
+      errors: Vec::new(),
+    };
+    let mut tasks = Vec::new();
+    for (index, value) in items.into_iter().enumerate() {
+      let parsed = serde_json::from_value::<TodoistItem>(value)
+        .map_err(|e| e.to_string())
+        .and_then(|item| {
+          let title = item.content.trim().to_string();
+          if item.checked || item.is_completed || item.is_deleted || title.is_empty() {
+            return Ok(None);
+          }
+          Ok(Some(Imported {
+            index,
+            title,
+            notes: item.description.filter(|d| !d.trim().is_empty()),
+            due: item.due.map(|d| parse_due(&d.date)).transpose()?,
+            priority: priority(item.priority),
+          }))
+        });
+      match parsed {
+        Ok(Some(task)) => tasks.push(task),
+        Ok(None) => report.skipped += 1,
+        Err(message) => report.errors.push(ItemError { index, message }),
+      }
+    }
+    if tasks.is_empty() {
+      report.failed = report.errors.len();
+      return Ok(report);
+    }
+
+    let (written, failures) = with_retry("import_todoist", || async {
+      let now = timestamp(&Utc::now());
+      let mut tx = db.pool().begin().await?;
+      let space_id = default_space(&mut tx).await?;
+      let list_id: i64 = sqlx::query_scalar(
+        "INSERT INTO lists (space_id, folder_id, name) VALUES (?, NULL, ?) RETURNING id;",
+      )
+      .bind(space_id)
+      .bind(IMPORT_LIST)
+      .fetch_one(&mut *tx)
+      .await?;
+
+      let mut written = Vec::new();
+      let mut failures = Vec::new();
+      for task in &tasks {
+        let inserted = sqlx::query_scalar::<_, i64>(
+          "INSERT INTO tasks \
+             (title, notes, done, created_at, updated_at, due, priority, list_id, sort_order) \
+           VALUES (?1, ?2, 0, ?3, ?3, ?4, ?5, ?6, \
+             (SELECT COALESCE(MAX(sort_order), 0) + 1 FROM tasks)) \
+           RETURNING id;",
+        )
+        .bind(&task.title)
+        .bind(&task.notes)
+        .bind(&now)
+        .bind(task.due.as_ref().map(timestamp))
+        .bind(task.priority.to_column())
+        .bind(list_id)
+        .fetch_one(&mut *tx)
+        .await;
+        match inserted {
+          Ok(id) => written.push(id),
+          Err(e) if is_busy(&e) => return Err(TxError::Db(e)),
+          Err(e) => failures.push(ItemError {
+            index: task.index,
+            message: e.to_string(),
+          }),
+        }
+      }
+      tx.commit().await?;
+      Ok((written, failures))
+    })
+    .await?;
+    // too broad to step back through, like the other imports
+    history.clear();
+
+    report.imported = written.len();
+    report.errors.extend(failures);
+    report.errors.sort_by_key(|e| e.index);
+    report.failed = report.errors.len();
+    log::info!(
+      "imported {} tasks from Todoist ({} skipped, {} failed)",
+      report.imported,
+      report.skipped,
+      report.failed
+    );
+    events::tasks_changed(&app, written, ChangeKind::Created);
+    Ok(report)
+  })
+  .await
+}
@@ -0,0 +1,591 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, SqliteConnection};
+use std::collections::{HashMap, HashSet};
+use tauri::{AppHandle, State};
+
+use crate::attachments::{Attachment, ATTACHMENT_COLUMNS};
+use crate::checklist;
+use crate::commands::{parse_timestamp, placeholders};
+use crate::db::{is_busy, with_retry, AppDb, TxError};
+use crate::error::{guard, AppError};
+use crate::events::{self, ChangeKind};
+use crate::history::History;
+use crate::lists::default_space;
+use crate::logging::log_error;
+use crate::migrations;
+use crate::models::{timestamp, Priority, RepeatRule, Task, TASK_COLUMNS, TASK_TAGS};
+use crate::tags;
+
+/// Oldest export layout `import_tasks` still understands.
+const MIN_IMPORT_VERSION: i64 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Export {
+  schema_version: i64,
+  exported_at: DateTime<Utc>,
+  tasks: Vec<Task>,
+  /// The lists those tasks are in. List ids only mean something in the
+  /// database they came from, so an import goes by name instead.
+  #[serde(default)]
+  lists: Vec<ExportedList>,
+  /// `None` in exports from before attachments were included, which then
+  /// leave the ones already here alone.
+  #[serde(default)]
+  attachments: Option<Vec<Attachment>>,
+}
+
+#[derive(Serialize, Deserialize, FromRow)]
+struct ExportedList {
+  id: i64,
+  name: String,
+}
+
+/// Read first so an incompatible file is reported as such, not as a confusing
+/// field-level parse error.
+#[derive(Deserialize)]
+struct ExportHeader {
+  schema_version: i64,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+  /// Drop every task not in the trash, then load the file. Trashed tasks stay
+  /// (they aren't exported, so the file has nothing to replace them with)
+  /// unless the file has a task with the same id. Checklists and attachments
+  /// go with their tasks and come back from the file.
+  Replace,
+  /// Upsert by id, keeping the existing row when it is the newer one.
+  Merge,
+}
+
+const INSERT_TASK: &str = "INSERT INTO tasks \
+     (id, title, notes, done, created_at, due, repeat, priority, updated_at, parent_id, \
+      list_id, completed_at, sort_order, archived) \
+   VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+
+const MERGE_TASK: &str = " ON CONFLICT(id) DO UPDATE SET \
+     title = excluded.title, notes = excluded.notes, done = excluded.done, \
+     created_at = excluded.created_at, due = excluded.due, repeat = excluded.repeat, \
+     priority = excluded.priority, updated_at = excluded.updated_at, \
+     parent_id = excluded.parent_id, list_id = excluded.list_id, \
+     completed_at = excluded.completed_at, sort_order = excluded.sort_order, \
+     archived = excluded.archived \
+   WHERE excluded.created_at >= tasks.created_at";
+
+/// What `export_tasks` returns; takes the pool directly, so it runs without an
+/// app (integration tests).
+pub async fn export(db: &AppDb) -> Result<String, AppError> {
+  let read = async {
+    let mut tx = db.pool().begin().await?;
+    let mut tasks = sqlx::query_as::<_, Task>(&format!(
+      "SELECT {TASK_COLUMNS}, {TASK_TAGS} FROM tasks WHERE deleted_at IS NULL ORDER BY id;"
+    ))
+    .fetch_all(&mut *tx)
+    .await?;
+    for task in &mut tasks {
+      task.checklist = Some(checklist::items(&mut tx, task.id).await?);
+    }
+    let attachments = sqlx::query_as::<_, Attachment>(&format!(
+      "SELECT {ATTACHMENT_COLUMNS} FROM attachments \
+       WHERE task_id IN (SELECT id FROM tasks WHERE deleted_at IS NULL) ORDER BY id;"
+    ))
+    .fetch_all(&mut *tx)
+    .await?;
+    let lists = sqlx::query_as::<_, ExportedList>(
+      "SELECT id, name FROM lists \
+       WHERE id IN (SELECT list_id FROM tasks WHERE deleted_at IS NULL) ORDER BY id;",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok::<_, sqlx::Error>((tasks, lists, attachments))
+  };
+  let (tasks, lists, attachments) = read.await.map_err(log_error("export_tasks"))?;
+
+  let export = Export {
+    schema_version: migrations::latest_version(),
+    exported_at: Utc::now(),
+    tasks,
+    lists,
+    attachments: Some(attachments),
+  };
+  serde_json::to_string_pretty(&export).map_err(log_error("export_tasks"))
+}
+
+/// Backup of every task not in the trash (with its tags, lists, checklist
+/// and attachments), as pretty-printed JSON.
+#[tauri::command]
+pub async fn export_tasks(db: State<'_, AppDb>) -> Result<String, AppError> {
+  guard("export_tasks", async { export(&db).await }).await
+}
+
+/// Where the exported lists' tasks go here: the first list with the same
+/// name, created at the top of the default space if there is none. Lists the
+/// file has no name for (older exports) keep their id if it exists here.
+async fn list_ids(
+  conn: &mut SqliteConnection,
+  lists: &[ExportedList],
+) -> sqlx::Result<HashMap<i64, i64>> {
+  let mut ids: HashMap<i64, i64> = sqlx::query_scalar::<_, i64>("SELECT id FROM lists;")
+    .fetch_all(&mut *conn)
+    .await?
+    .into_iter()
+    .map(|id| (id, id))
+    .collect();
+  for list in lists {
+    let existing: Option<i64> =
+      sqlx::query_scalar("SELECT id FROM lists WHERE name = ? ORDER BY id LIMIT 1;")
+        .bind(&list.name)
+        .fetch_optional(&mut *conn)
+        .await?;
+    let id = match existing {
+      Some(id) => id,
+      None => {
+        let space_id = default_space(conn).await?;
+        sqlx::query_scalar(
+          "INSERT INTO lists (space_id, folder_id, name) VALUES (?, NULL, ?) RETURNING id;",
+        )
+        .bind(space_id)
+        .bind(&list.name)
+        .fetch_one(&mut *conn)
+        .await?
+      }
+    };
+    ids.insert(list.id, id);
+  }
+  Ok(ids)
+}
+
+/// What `import_tasks` does, minus the change event; returns the ids written.
+pub async fn import(
+  db: &AppDb,
+  history: &History,
+  json: &str,
+  mode: ImportMode,
+) -> Result<Vec<i64>, AppError> {
+  let header: ExportHeader = serde_json::from_str(json)
+    .map_err(|e| AppError::Validation(format!("not a task export: {e}")))?;
+  let latest = migrations::latest_version();
+  if header.schema_version > latest {
+    return Err(AppError::Validation(format!(
+      "this export is from a newer version of Tasks (schema {}, this app supports up to {latest})",
+      header.schema_version
+    )));
+  }
+  if header.schema_version < MIN_IMPORT_VERSION {
+    return Err(AppError::Validation(format!(
+      "unsupported export schema version {}",
+      header.schema_version
+    )));
+  }
+  let export: Export = serde_json::from_str(json)
+    .map_err(|e| AppError::Validation(format!("malformed task export: {e}")))?;
+
+  let sql = match mode {
+    ImportMode::Replace => format!("{INSERT_TASK};"),
+    ImportMode::Merge => format!("{INSERT_TASK}{MERGE_TASK};"),
+  };
+
+  // parents outside the file (e.g. still in the trash) are dropped, and
+  // checks are deferred since children may come before their parent
+  let ids: HashSet<i64> = export.tasks.iter().map(|t| t.id).collect();
+  let now = timestamp(&Utc::now());
+  let written = with_retry("import_tasks", || async {
+    let mut tx = db.pool().begin().await?;
+    sqlx::query("PRAGMA defer_foreign_keys = ON;")
+      .execute(&mut *tx)
+      .await?;
+    if mode == ImportMode::Replace {
+      let replaced = format!(
+        "DELETE FROM tasks WHERE deleted_at IS NULL OR id IN ({});",
+        placeholders(ids.len())
+      );
+      let mut delete = sqlx::query(&replaced);
+      for id in &ids {
+        delete = delete.bind(id);
+      }
+      delete.execute(&mut *tx).await?;
+    }
+    let list_ids = list_ids(&mut tx, &export.lists).await?;
+    let mut written = Vec::new();
+    for task in &export.tasks {
+      let result = sqlx::query(&sql)
+        .bind(task.id)
+        .bind(&task.title)
+        .bind(&task.notes)
+        .bind(task.done)
+        .bind(timestamp(&task.created_at))
+        .bind(task.due.as_ref().map(timestamp))
+        .bind(RepeatRule::to_column(task.repeat))
+        .bind(task.priority.to_column())
+        .bind(&now)
+        .bind(task.parent_id.filter(|p| ids.contains(p)))
+        .bind(task.list_id.and_then(|id| list_ids.get(&id).copied()))
+        .bind(
+          task
+            .completed_at
+            .filter(|_| task.done)
+            .as_ref()
+            .map(timestamp),
+        )
+        .bind(task.sort_order)
+        .bind(task.archived)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match e {
+          // a lock is worth another attempt; anything else is down to this task
+          e if is_busy(&e) => TxError::Db(e),
+          e => AppError::Validation(format!("task {}: {e}", task.id)).into(),
+        })?;
+      if result.rows_affected() == 0 {
+        continue;
+      }
+
+      sqlx::query("DELETE FROM task_tags WHERE task_id = ?;")
+        .bind(task.id)
+        .execute(&mut *tx)
+        .await?;
+      for name in &task.tags {
+        let name = tags::normalize(name)
+          .map_err(|e| AppError::Validation(format!("task {}: {e}", task.id)))?;
+        tags::attach(&mut tx, task.id, &name).await?;
+      }
+      if let Some(items) = &task.checklist {
+        sqlx::query("DELETE FROM checklist_items WHERE task_id = ?;")
+          .bind(task.id)
+          .execute(&mut *tx)
+          .await?;
+        for (position, item) in (0_i64..).zip(items) {
+          sqlx::query(
+            "INSERT INTO checklist_items (task_id, text, done, position) VALUES (?, ?, ?, ?);",
+          )
+          .bind(task.id)
+          .bind(&item.text)
+          .bind(item.done)
+          .bind(position)
+          .execute(&mut *tx)
+          .await?;
+        }
+      }
+      written.push(task.id);
+    }
+
+    if let Some(attachments) = &export.attachments {
+      for id in &written {
+        sqlx::query("DELETE FROM attachments WHERE task_id = ?;")
+          .bind(id)
+          .execute(&mut *tx)
+          .await?;
+      }
+      // only the reference comes back; the file is wherever it was
+      for attachment in attachments.iter().filter(|a| written.contains(&a.task_id)) {
+        sqlx::query(
+          "INSERT INTO attachments (task_id, file_name, path, size, modified_at, attached_at) \
+           VALUES (?, ?, ?, ?, ?, ?);",
+        )
+        .bind(attachment.task_id)
+        .bind(&attachment.file_name)
+        .bind(&attachment.path)
+        .bind(attachment.size)
+        .bind(attachment.modified_at.as_ref().map(timestamp))
+        .bind(timestamp(&attachment.attached_at))
+        .execute(&mut *tx)
+        .await?;
+      }
+    }
+    tx.commit().await?;
+    Ok(written)
+  })
+  .await?;
+  // too broad to step back through, and earlier entries may not apply now
+  history.clear();
+  Ok(written)
+}
+
+/// Loads an `export_tasks` file; returns how many tasks were written.
+#[tauri::command]
+pub async fn import_tasks(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  json: String,
+  mode: ImportMode,
+) -> Result<usize, AppError> {
+  guard("import_tasks", async {
+    let written = import(&db, &history, &json, mode).await?;
+    let count = written.len();
+    events::tasks_changed(&app, written, ChangeKind::Updated);
+    Ok(count)
+  })
+  .await
+}
+
+const CSV_HEADER: [&str; 8] = [
+  "id",
+  "title",
+  "notes",
+  "done",
+  "created_at",
+  "due",
+  "repeat",
+  "priority",
+];
+
+/// A CSV row that could not be imported, by 1-based line in the file.
+#[derive(Serialize, Clone, Debug)]
+pub struct CsvRowError {
+  pub line: u64,
+  pub message: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct CsvImport {
+  pub imported: usize,
+  pub errors: Vec<CsvRowError>,
+}
+
+/// Every task not in the trash as RFC 4180 CSV, header row first.
+#[tauri::command]
+pub async fn export_tasks_csv(db: State<'_, AppDb>) -> Result<String, AppError> {
+  guard("export_tasks_csv", async {
+    let tasks = sqlx::query_as::<_, Task>(&format!(
+      "SELECT {TASK_COLUMNS} FROM tasks WHERE deleted_at IS NULL ORDER BY id;"
+    ))
+    .fetch_all(&db.pool())
+    .await
+    .map_err(log_error("export_tasks_csv"))?;
+
+    let mut out = csv::Writer::from_writer(Vec::new());
+    out
+      .write_record(CSV_HEADER)
+      .map_err(log_error("export_tasks_csv"))?;
+    for task in &tasks {
+      out
+        .write_record([
+          task.id.to_string(),
+          task.title.clone(),
+          task.notes.clone().unwrap_or_default(),
+          task.done.to_string(),
+          timestamp(&task.created_at),
+          task.due.as_ref().map(timestamp).unwrap_or_default(),
+          RepeatRule::to_column(task.repeat).to_string(),
+          task.priority.name().to_string(),
+        ])
+        .map_err(log_error("export_tasks_csv"))?;
+    }
+    let bytes = out
+      .into_inner()
+      .map_err(|e| log_error("export_tasks_csv")(e.into_error()))?;
+    // every field went in as a `String`, so this can't fail
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+  })
+  .await
+}
+
+/// A parsed CSV row. `id` is present when the file came from
+/// `export_tasks_csv`, in which case the row updates that task in place, as
+/// long as it's still there (not in the trash) and has the same `created_at`.
+struct CsvTask {
+  id: Option<i64>,
+  title: String,
+  notes: Option<String>,
+  done: bool,
+  created_at: DateTime<Utc>,
+  due: Option<DateTime<Utc>>,
+  repeat: Option<RepeatRule>,
+  priority: Priority,
+}
+
+/// Maps header names to column positions; only `title` is required.
+struct CsvColumns {
+  id: Option<usize>,
+  title: usize,
+  notes: Option<usize>,
+  done: Option<usize>,
+  created_at: Option<usize>,
+  due: Option<usize>,
+  repeat: Option<usize>,
+  priority: Option<usize>,
+}
+
+impl CsvColumns {
+  fn from_header(header: &csv::StringRecord) -> Result<Self, String> {
+    let find = |name: &str| {
+      header
+        .iter()
+        .position(|h| h.trim().eq_ignore_ascii_case(name))
+    };
+    Ok(Self {
+      id: find("id"),
+      title: find("title").ok_or("CSV header has no \"title\" column")?,
+      notes: find("notes"),
+      done: find("done"),
+      created_at: find("created_at"),
+      due: find("due"),
+      repeat: find("repeat"),
+      priority: find("priority"),
+    })
+  }
+
+  fn parse(&self, record: &csv::StringRecord) -> Result<CsvTask, String> {
+    let field = |idx: Option<usize>| {
+      idx
+        .and_then(|i| record.get(i))
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+    };
+
+    let title = field(Some(self.title)).ok_or("title is empty")?.to_string();
+    let id = field(self.id)
+      .map(|v| v.parse::<i64>().map_err(|_| format!("invalid id {v:?}")))
+      .transpose()?;
+    let done = match field(self.done).map(str::to_ascii_lowercase).as_deref() {
+      None | Some("false" | "0" | "no") => false,
+      Some("true" | "1" | "yes" | "x") => true,
+      Some(other) => return Err(format!("invalid done value {other:?}")),
+    };
+    let created_at = field(self.created_at)
+      .map(|v| parse_timestamp("created_at", v).map_err(|e| e.to_string()))
+      .transpose()?
+      .unwrap_or_else(Utc::now);
+    let due = field(self.due)
+      .map(|v| parse_timestamp("due", v).map_err(|e| e.to_string()))
+      .transpose()?;
+    let repeat = match field(self.repeat) {
+      None | Some("none") => None,
+      Some(v) => Some(RepeatRule::from_column(v).ok_or_else(|| format!("invalid repeat {v:?}"))?),
+    };
+
+    let priority = match field(self.priority) {
+      None => Priority::default(),
+      Some(v) => Priority::from_name(v).ok_or_else(|| format!("invalid priority {v:?}"))?,
+    };
+
+    Ok(CsvTask {
+      id,
+      title,
+      notes: field(self.notes).map(str::to_string),
+      done,
+      created_at,
+      due,
+      repeat,
+      priority,
+    })
+  }
+}
+
+/// What `import_tasks_csv` does, minus the change event; returns the ids
+/// written and the rows that weren't.
+pub async fn import_csv(
+  db: &AppDb,
+  history: &History,
+  csv: &str,
+) -> Result<(Vec<i64>, Vec<CsvRowError>), AppError> {
+  let mut reader = csv::ReaderBuilder::new().from_reader(csv.as_bytes());
+  let header = reader
+    .headers()
+    .map_err(|e| AppError::Validation(format!("unreadable CSV header: {e}")))?;
+  let columns = CsvColumns::from_header(header).map_err(AppError::Validation)?;
+
+  let mut rows = Vec::new();
+  let mut errors = Vec::new();
+  for record in reader.records() {
+    let parsed = record.map_err(|e| (e.position().map(|p| p.line()), e.to_string()));
+    let parsed = parsed.and_then(|r| {
+      columns
+        .parse(&r)
+        .map_err(|e| (r.position().map(|p| p.line()), e))
+    });
+    match parsed {
+      Ok(row) => rows.push(row),
+      Err((line, message)) => errors.push(CsvRowError {
+        line: line.unwrap_or_default(),
+        message,
+      }),
+    }
+  }
+
+  // an id only names the same task if it was created at the same moment;
+  // otherwise it's from another database, or the task was deleted since
+  let update = "UPDATE tasks SET \
+       title = ?, notes = ?, done = ?, due = ?, repeat = ?, priority = ?, updated_at = ? \
+     WHERE id = ? AND created_at = ? AND deleted_at IS NULL \
+     RETURNING id;";
+  let insert = format!("{INSERT_TASK} RETURNING id;");
+  let now = timestamp(&Utc::now());
+  let written = with_retry("import_tasks_csv", || async {
+    let mut tx = db.pool().begin().await?;
+    let mut written = Vec::new();
+    for row in &rows {
+      let created_at = timestamp(&row.created_at);
+      let due = row.due.as_ref().map(timestamp);
+      let updated: Option<i64> = match row.id {
+        Some(id) => {
+          sqlx::query_scalar(update)
+            .bind(&row.title)
+            .bind(&row.notes)
+            .bind(row.done)
+            .bind(&due)
+            .bind(RepeatRule::to_column(row.repeat))
+            .bind(row.priority.to_column())
+            .bind(&now)
+            .bind(id)
+            .bind(&created_at)
+            .fetch_optional(&mut *tx)
+            .await?
+        }
+        None => None,
+      };
+      let id = match updated {
+        Some(id) => id,
+        None => {
+          sqlx::query_scalar(&insert)
+            .bind(None::<i64>)
+            .bind(&row.title)
+            .bind(&row.notes)
+            .bind(row.done)
+            .bind(&created_at)
+            .bind(&due)
+            .bind(RepeatRule::to_column(row.repeat))
+            .bind(row.priority.to_column())
+            .bind(&now)
+            .bind(None::<i64>)
+            .bind(None::<i64>)
+            // no column for it; the completion trigger stamps rows flipped to done
+            .bind(None::<String>)
+            .bind(0.0)
+            .bind(false)
+            .fetch_one(&mut *tx)
+            .await?
+        }
+      };
+      written.push(id);
+    }
+    tx.commit().await?;
+    Ok(written)
+  })
+  .await?;
+  // too broad to step back through, and earlier entries may not apply now
+  history.clear();
+  Ok((written, errors))
+}
+
+/// Imports CSV rows, matching columns by header name. Bad rows are reported
+/// back by line number; the rest are still imported.
+#[tauri::command]
+pub async fn import_tasks_csv(
+  app: AppHandle,
+  db: State<'_, AppDb>,
+  history: State<'_, History>,
+  csv: String,
+) -> Result<CsvImport, AppError> {
+  guard("import_tasks_csv", async {
+    let (written, errors) = import_csv(&db, &history, &csv).await?;
+    let imported = written.len();
+    events::tasks_changed(&app, written, ChangeKind::Updated);
+    Ok(CsvImport { imported, errors })
+  })
+  .await
+}
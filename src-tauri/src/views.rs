@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{State, Window};
+
+use crate::error::{guard_sync, AppError};
+use crate::models::{SortBy, TaskFilter};
+
+/// What a window is showing, kept here so it survives a reload of the page.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ViewState {
+  pub filter: TaskFilter,
+  pub sort: SortBy,
+  pub list_id: Option<i64>,
+}
+
+/// Each open window's `ViewState`, by window label. Managed state; only kept
+/// in memory, and a window's entry goes when it closes.
+#[derive(Default)]
+pub struct Views(Mutex<HashMap<String, ViewState>>);
+
+impl Views {
+  pub fn forget(&self, label: &str) {
+    self
+      .0
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .remove(label);
+  }
+}
+
+/// Stores the calling window's view; a window can't write another's.
+#[tauri::command]
+pub fn set_view_state(
+  window: Window,
+  views: State<'_, Views>,
+  state: ViewState,
+) -> Result<(), AppError> {
+  guard_sync("set_view_state", || {
+    views
+      .0
+      .lock()
+      .unwrap_or_else(|e| e.into_inner())
+      .insert(window.label().to_string(), state);
+    Ok(())
+  })
+}
+
+/// The calling window's view; `None` until it has stored one.
+#[tauri::command]
+pub fn get_view_state(
+  window: Window,
+  views: State<'_, Views>,
+) -> Result<Option<ViewState>, AppError> {
+  guard_sync("get_view_state", || {
+    Ok(
+      views
+        .0
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(window.label())
+        .cloned(),
+    )
+  })
+}
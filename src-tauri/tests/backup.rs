@@ -0,0 +1,82 @@
+mod common;
+
+use app_lib::backup;
+use app_lib::db::AppDb;
+use app_lib::history::History;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, Connection};
+use std::path::Path;
+
+use common::{scratch_path, seed};
+
+async fn titles(db: &AppDb) -> Vec<String> {
+  sqlx::query_scalar("SELECT title FROM tasks ORDER BY id;")
+    .fetch_all(&db.pool())
+    .await
+    .unwrap()
+}
+
+fn beside(path: &Path, suffix: &str) -> bool {
+  let mut side = path.as_os_str().to_owned();
+  side.push(suffix);
+  Path::new(&side).exists()
+}
+
+#[tokio::test]
+async fn restoring_a_snapshot_swaps_in_its_tasks() {
+  let current = scratch_path("restore-current", "db");
+  let snapshot = scratch_path("restore-snapshot", "db");
+  let history = History::default();
+  let db = AppDb::open(&current.0, None).await.unwrap();
+  seed(&db, &history, 3).await;
+  let old = AppDb::open(&snapshot.0, None).await.unwrap();
+  seed(&old, &history, 1).await;
+  old.pool().close().await;
+
+  backup::restore_file(&db, &snapshot.0).await.unwrap();
+
+  assert_eq!(titles(&db).await, ["task 1"]);
+  assert!(!beside(&current.0, ".pre-restore"));
+  assert!(!beside(&current.0, ".restore"));
+}
+
+#[tokio::test]
+async fn a_snapshot_that_fails_to_migrate_leaves_the_database_as_it_was() {
+  let current = scratch_path("restore-failing", "db");
+  let snapshot = scratch_path("restore-dirty", "db");
+  let history = History::default();
+  let db = AppDb::open(&current.0, None).await.unwrap();
+  seed(&db, &history, 2).await;
+  // a migration recorded as started but never finished, which sqlx won't run
+  // past
+  let mut conn = SqliteConnectOptions::new()
+    .filename(&snapshot.0)
+    .create_if_missing(true)
+    .connect()
+    .await
+    .unwrap();
+  sqlx::raw_sql(
+    "CREATE TABLE _sqlx_migrations (
+       version BIGINT PRIMARY KEY,
+       description TEXT NOT NULL,
+       installed_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+       success BOOLEAN NOT NULL,
+       checksum BLOB NOT NULL,
+       execution_time BIGINT NOT NULL
+     );
+     INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+     VALUES (1, 'create tasks', 0, x'00', 0);",
+  )
+  .execute(&mut conn)
+  .await
+  .unwrap();
+  conn.close().await.unwrap();
+
+  assert!(backup::restore_file(&db, &snapshot.0).await.is_err());
+
+  assert_eq!(titles(&db).await, ["task 1", "task 2"]);
+  seed(&db, &history, 1).await;
+  assert_eq!(titles(&db).await.len(), 3);
+  assert!(!beside(&current.0, ".pre-restore"));
+  assert!(!beside(&current.0, ".restore"));
+}
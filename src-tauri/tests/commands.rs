@@ -0,0 +1,343 @@
+mod common;
+
+use app_lib::commands;
+use app_lib::error::AppError;
+use app_lib::events::ChangeKind;
+use app_lib::models::{NewTask, RepeatRule, SortBy, Task, TaskFilter, TaskPatch};
+use app_lib::notes::{self, Notes};
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{memory_db, seed, titled};
+
+fn ids(tasks: &[Task]) -> Vec<i64> {
+  tasks.iter().map(|t| t.id).collect()
+}
+
+#[tokio::test]
+async fn lists_created_tasks_in_order() {
+  let (db, history) = memory_db().await;
+  let seeded = seed(&db, &history, 5).await;
+
+  let listed = commands::list(&db, TaskFilter::Active, SortBy::CreatedAsc, None, false)
+    .await
+    .unwrap();
+  assert_eq!(ids(&listed), ids(&seeded));
+  assert_eq!(listed[0].title, "task 1");
+  assert!(listed.iter().all(|t| !t.done && t.deleted_at.is_none()));
+
+  let newest_first = commands::list(&db, TaskFilter::All, SortBy::CreatedDesc, None, false)
+    .await
+    .unwrap();
+  assert_eq!(
+    ids(&newest_first),
+    ids(&seeded).into_iter().rev().collect::<Vec<_>>()
+  );
+}
+
+#[tokio::test]
+async fn create_trims_the_title_and_rejects_an_empty_one() {
+  let (db, history) = memory_db().await;
+  let task = commands::create(&db, &history, titled("  buy milk "))
+    .await
+    .unwrap();
+  assert_eq!(task.title, "buy milk");
+
+  let err = commands::create(&db, &history, titled("   "))
+    .await
+    .unwrap_err();
+  assert!(matches!(err, AppError::Validation(_)), "{err:?}");
+  let err = commands::create(
+    &db,
+    &history,
+    NewTask {
+      due: Some("next tuesday".into()),
+      ..titled("dated")
+    },
+  )
+  .await
+  .unwrap_err();
+  assert!(matches!(err, AppError::Validation(_)), "{err:?}");
+}
+
+#[tokio::test]
+async fn toggling_moves_a_task_between_filters() {
+  let (db, history) = memory_db().await;
+  let seeded = seed(&db, &history, 2).await;
+
+  let toggled = commands::toggle(&db, &history, seeded[0].id, false)
+    .await
+    .unwrap();
+  assert!(toggled.task.done);
+  assert!(toggled.task.completed_at.is_some());
+  assert!(toggled.next.is_none());
+
+  let active = commands::list(&db, TaskFilter::Active, SortBy::CreatedAsc, None, false)
+    .await
+    .unwrap();
+  assert_eq!(ids(&active), vec![seeded[1].id]);
+  let completed = commands::list(&db, TaskFilter::Completed, SortBy::CreatedAsc, None, false)
+    .await
+    .unwrap();
+  assert_eq!(ids(&completed), vec![seeded[0].id]);
+
+  let toggled = commands::toggle(&db, &history, seeded[0].id, false)
+    .await
+    .unwrap();
+  assert!(!toggled.task.done);
+  assert!(toggled.task.completed_at.is_none());
+}
+
+#[tokio::test]
+async fn completing_a_recurring_task_spawns_the_next_one() {
+  let (db, history) = memory_db().await;
+  let task = commands::create(
+    &db,
+    &history,
+    NewTask {
+      due: Some("2030-01-07T09:00:00.000Z".into()),
+      repeat: Some(RepeatRule::Weekly),
+      ..titled("standup notes")
+    },
+  )
+  .await
+  .unwrap();
+
+  let toggled = commands::toggle(&db, &history, task.id, false)
+    .await
+    .unwrap();
+  assert_eq!(toggled.task.repeat, None);
+  let next = toggled.next.expect("a next occurrence");
+  assert_eq!(next.title, "standup notes");
+  assert_eq!(next.repeat, Some(RepeatRule::Weekly));
+  assert!(!next.done);
+  assert!(next.due > task.due);
+
+  // un-completing and re-completing doesn't spawn a second one
+  commands::toggle(&db, &history, task.id, false)
+    .await
+    .unwrap();
+  let again = commands::toggle(&db, &history, task.id, false)
+    .await
+    .unwrap();
+  assert!(again.next.is_none());
+}
+
+fn weekly(title: &str) -> NewTask {
+  NewTask {
+    due: Some("2030-01-07T09:00:00.000Z".into()),
+    repeat: Some(RepeatRule::Weekly),
+    ..titled(title)
+  }
+}
+
+#[tokio::test]
+async fn bulk_completing_a_recurring_task_spawns_the_next_one() {
+  let (db, history) = memory_db().await;
+  let recurring = commands::create(&db, &history, weekly("standup notes"))
+    .await
+    .unwrap();
+  let plain = commands::create(&db, &history, titled("one-off"))
+    .await
+    .unwrap();
+
+  let (completed, spawned) = commands::complete_all(&db, &history, &[recurring.id, plain.id])
+    .await
+    .unwrap();
+  assert_eq!(completed.len(), 2);
+  assert_eq!(spawned.len(), 1);
+  assert_eq!(spawned[0].title, "standup notes");
+  assert_eq!(spawned[0].repeat, Some(RepeatRule::Weekly));
+  assert_eq!(
+    spawned[0].due.unwrap().to_rfc3339(),
+    "2030-01-14T09:00:00+00:00"
+  );
+
+  let listed = commands::list(&db, TaskFilter::All, SortBy::CreatedAsc, None, false)
+    .await
+    .unwrap();
+  assert_eq!(listed.len(), 3);
+  assert_eq!(listed[0].repeat, None);
+}
+
+#[tokio::test]
+async fn completing_a_recurring_task_through_update_spawns_the_next_one() {
+  let (db, history) = memory_db().await;
+  let task = commands::create(&db, &history, weekly("standup notes"))
+    .await
+    .unwrap();
+
+  let patch = TaskPatch {
+    done: Some(true),
+    ..TaskPatch::default()
+  };
+  let (updated, next) = commands::update(&db, &history, &Notes::default(), task.id, patch, None)
+    .await
+    .unwrap();
+  assert_eq!(updated.repeat, None);
+  let next = next.expect("a next occurrence");
+  assert_eq!(next.repeat, Some(RepeatRule::Weekly));
+  assert!(next.due > task.due);
+}
+
+#[tokio::test]
+async fn toggling_with_cascade_completes_subtasks() {
+  let (db, history) = memory_db().await;
+  let parent = commands::create(&db, &history, titled("parent"))
+    .await
+    .unwrap();
+  let child = commands::create(
+    &db,
+    &history,
+    NewTask {
+      parent_id: Some(parent.id),
+      ..titled("child")
+    },
+  )
+  .await
+  .unwrap();
+
+  let toggled = commands::toggle(&db, &history, parent.id, true)
+    .await
+    .unwrap();
+  assert_eq!(toggled.completed_subtasks, vec![child.id]);
+  let active = commands::list(&db, TaskFilter::Active, SortBy::CreatedAsc, None, false)
+    .await
+    .unwrap();
+  assert!(active.is_empty());
+}
+
+#[tokio::test]
+async fn missing_tasks_are_not_found() {
+  let (db, history) = memory_db().await;
+  let err = commands::toggle(&db, &history, 42, false)
+    .await
+    .unwrap_err();
+  assert_eq!(err, AppError::NotFound);
+  let err = commands::delete(&db, &history, 42, false, false)
+    .await
+    .unwrap_err();
+  assert_eq!(err, AppError::NotFound);
+}
+
+#[tokio::test]
+async fn soft_delete_trashes_and_hard_delete_removes() {
+  let (db, history) = memory_db().await;
+  let seeded = seed(&db, &history, 3).await;
+
+  commands::delete(&db, &history, seeded[0].id, false, false)
+    .await
+    .unwrap();
+  let trashed: Option<String> = sqlx::query_scalar("SELECT deleted_at FROM tasks WHERE id = ?;")
+    .bind(seeded[0].id)
+    .fetch_one(&db.pool())
+    .await
+    .unwrap();
+  assert!(trashed.is_some());
+
+  commands::delete(&db, &history, seeded[1].id, true, false)
+    .await
+    .unwrap();
+  let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tasks WHERE id = ?;")
+    .bind(seeded[1].id)
+    .fetch_one(&db.pool())
+    .await
+    .unwrap();
+  assert_eq!(left, 0);
+
+  let listed = commands::list(&db, TaskFilter::All, SortBy::CreatedAsc, None, false)
+    .await
+    .unwrap();
+  assert_eq!(ids(&listed), vec![seeded[2].id]);
+}
+
+#[tokio::test]
+async fn deleting_a_parent_moves_or_takes_its_subtasks() {
+  let (db, history) = memory_db().await;
+  let parent = commands::create(&db, &history, titled("parent"))
+    .await
+    .unwrap();
+  let child = commands::create(
+    &db,
+    &history,
+    NewTask {
+      parent_id: Some(parent.id),
+      ..titled("child")
+    },
+  )
+  .await
+  .unwrap();
+
+  // without cascade the child moves up to the deleted task's own parent
+  let (subtasks, kind) = commands::delete(&db, &history, parent.id, false, false)
+    .await
+    .unwrap();
+  assert_eq!(subtasks, vec![child.id]);
+  assert!(matches!(kind, ChangeKind::Updated));
+  let listed = commands::list(&db, TaskFilter::All, SortBy::CreatedAsc, None, false)
+    .await
+    .unwrap();
+  assert_eq!(ids(&listed), vec![child.id]);
+  assert_eq!(listed[0].parent_id, None);
+
+  let other = commands::create(&db, &history, titled("other"))
+    .await
+    .unwrap();
+  let grandchild = commands::create(
+    &db,
+    &history,
+    NewTask {
+      parent_id: Some(other.id),
+      ..titled("grandchild")
+    },
+  )
+  .await
+  .unwrap();
+  let (subtasks, kind) = commands::delete(&db, &history, other.id, true, true)
+    .await
+    .unwrap();
+  assert_eq!(subtasks, vec![grandchild.id]);
+  assert!(matches!(kind, ChangeKind::Deleted));
+  let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tasks;")
+    .fetch_one(&db.pool())
+    .await
+    .unwrap();
+  // the trashed parent and the child that moved up
+  assert_eq!(left, 2);
+}
+
+#[tokio::test]
+async fn updated_notes_win_over_ones_still_waiting_to_be_saved() {
+  let (db, history) = memory_db().await;
+  let (db, history) = (Arc::new(db), Arc::new(history));
+  let task = seed(&db, &history, 1).await.remove(0);
+  let queue = Notes::default();
+
+  let (writer_db, writer_history) = (Arc::clone(&db), Arc::clone(&history));
+  queue.save(task.id, Some("typed".into()), move |text| async move {
+    notes::write_notes(&writer_db, &writer_history, task.id, text).await
+  });
+  let patch = TaskPatch {
+    notes: Some(Some("updated".into())),
+    ..TaskPatch::default()
+  };
+  commands::update(&db, &history, &queue, task.id, patch, None)
+    .await
+    .unwrap();
+  // past the debounce, when the typed value would have been written
+  tokio::time::sleep(Duration::from_millis(800)).await;
+
+  let listed = commands::list(&db, TaskFilter::All, SortBy::CreatedAsc, None, false)
+    .await
+    .unwrap();
+  assert_eq!(listed[0].notes.as_deref(), Some("updated"));
+}
+
+#[test]
+fn a_wrong_passphrase_has_its_own_error_kind() {
+  // `unlock` in db.ts asks again on this kind, and gives up on any other
+  assert_eq!(
+    serde_json::to_value(AppError::InvalidPassphrase).unwrap(),
+    serde_json::json!({ "kind": "invalid_passphrase" })
+  );
+}
@@ -0,0 +1,75 @@
+// each test file uses only some of these
+#![allow(dead_code)]
+
+use app_lib::commands;
+use app_lib::db::AppDb;
+use app_lib::history::History;
+use app_lib::migrations;
+use app_lib::models::{NewTask, Task};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// A fully migrated database that lives in memory for as long as the pool
+/// does. The pool holds on to its single connection: every new connection to
+/// `sqlite::memory:` would open a different, empty database.
+pub async fn memory_db() -> (AppDb, History) {
+  let options = SqliteConnectOptions::from_str("sqlite::memory:")
+    .unwrap()
+    .foreign_keys(true);
+  let pool = SqlitePoolOptions::new()
+    .max_connections(1)
+    .idle_timeout(None)
+    .max_lifetime(None)
+    .connect_with(options)
+    .await
+    .unwrap();
+  migrations::apply(&mut pool.acquire().await.unwrap())
+    .await
+    .unwrap();
+  (AppDb::new(pool), History::default())
+}
+
+/// A path in the temp directory for one test, gone when the test ends (along
+/// with any `-wal` and `-shm` files SQLite kept next to it).
+pub struct Scratch(pub PathBuf);
+
+impl Drop for Scratch {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.0);
+    for sidecar in ["-wal", "-shm"] {
+      let mut path = self.0.clone().into_os_string();
+      path.push(sidecar);
+      let _ = fs::remove_file(path);
+    }
+  }
+}
+
+/// A `Scratch` path named after the test, with nothing there yet.
+pub fn scratch_path(name: &str, ext: &str) -> Scratch {
+  let path = std::env::temp_dir().join(format!("tasks-{name}-{}.{ext}", std::process::id()));
+  let scratch = Scratch(path);
+  let _ = fs::remove_file(&scratch.0);
+  scratch
+}
+
+/// A task with just a title.
+pub fn titled(title: &str) -> NewTask {
+  NewTask {
+    title: title.to_string(),
+    ..NewTask::default()
+  }
+}
+
+/// Creates `n` tasks, "task 1" to "task n" in that order, and returns them.
+pub async fn seed(db: &AppDb, history: &History, n: usize) -> Vec<Task> {
+  let mut tasks = Vec::with_capacity(n);
+  for i in 1..=n {
+    let task = commands::create(db, history, titled(&format!("task {i}")))
+      .await
+      .unwrap();
+    tasks.push(task);
+  }
+  tasks
+}
@@ -0,0 +1,46 @@
+use app_lib::due::parse_due;
+use app_lib::error::AppError;
+
+/// US Eastern, as a POSIX rule so it doesn't depend on the system's tz
+/// database: DST starts on 2024-03-10 at 2am. This file is its own test
+/// binary, so nothing else runs on the changed zone.
+fn in_new_york() {
+  std::env::set_var("TZ", "EST5EDT,M3.2.0,M11.1.0");
+}
+
+/// 9am on Monday 2024-03-04, the week DST starts.
+fn parse(input: &str) -> Result<String, AppError> {
+  parse_due(input.into(), Some("2024-03-04T14:00:00.000Z".into()))
+}
+
+#[test]
+fn day_phrases_resolve_against_now() {
+  in_new_york();
+  // the end of the day, local time
+  assert_eq!(parse("today").unwrap(), "2024-03-05T04:59:00.000Z");
+  assert_eq!(parse("tomorrow").unwrap(), "2024-03-06T04:59:00.000Z");
+  assert_eq!(parse("friday").unwrap(), "2024-03-09T04:59:00.000Z");
+  assert_eq!(parse("tomorrow 3pm").unwrap(), "2024-03-05T20:00:00.000Z");
+  // a week on, never today; past the switch, so an hour less from UTC
+  assert_eq!(parse("next monday").unwrap(), "2024-03-12T03:59:00.000Z");
+  // a plain duration, which DST doesn't stretch
+  assert_eq!(parse("in 3 days").unwrap(), "2024-03-07T14:00:00.000Z");
+  // 2:30am doesn't exist that Sunday; it's taken an hour later
+  assert_eq!(parse("sunday 2:30am").unwrap(), "2024-03-10T07:30:00.000Z");
+}
+
+#[test]
+fn unparseable_due_phrases_have_their_own_error_kind() {
+  in_new_york();
+  let err = parse("whenever").unwrap_err();
+  assert_eq!(
+    err,
+    AppError::UnrecognizedDate {
+      input: "whenever".into()
+    }
+  );
+  assert_eq!(
+    serde_json::to_value(&err).unwrap(),
+    serde_json::json!({ "kind": "unrecognized_date", "message": { "input": "whenever" } })
+  );
+}
@@ -0,0 +1,142 @@
+mod common;
+
+use app_lib::attachments;
+use app_lib::checklist;
+use app_lib::commands;
+use app_lib::db::AppDb;
+use app_lib::history;
+use std::fs;
+
+use common::{memory_db, scratch_path, seed, Scratch};
+
+/// A file to attach, gone when the test ends.
+fn attachable(name: &str) -> Scratch {
+  let file = scratch_path(name, "txt");
+  fs::write(&file.0, "attached").unwrap();
+  file
+}
+
+async fn checklist_of(db: &AppDb, task_id: i64) -> Vec<(i64, String, bool)> {
+  sqlx::query_as("SELECT id, text, done FROM checklist_items WHERE task_id = ? ORDER BY position;")
+    .bind(task_id)
+    .fetch_all(&db.pool())
+    .await
+    .unwrap()
+}
+
+async fn attachments_of(db: &AppDb, task_id: i64) -> Vec<(i64, String)> {
+  sqlx::query_as("SELECT id, file_name FROM attachments WHERE task_id = ? ORDER BY id;")
+    .bind(task_id)
+    .fetch_all(&db.pool())
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn undoing_a_hard_delete_brings_back_checklist_and_attachments() {
+  let (db, history) = memory_db().await;
+  let task = seed(&db, &history, 1).await.remove(0);
+  let file = attachable("hard-delete");
+  checklist::add(&db, &history, task.id, "eggs")
+    .await
+    .unwrap();
+  checklist::add(&db, &history, task.id, "flour")
+    .await
+    .unwrap();
+  attachments::add(&db, &history, task.id, &file.0.to_string_lossy())
+    .await
+    .unwrap();
+  let items = checklist_of(&db, task.id).await;
+  let attached = attachments_of(&db, task.id).await;
+  assert_eq!(items.len(), 2);
+  assert_eq!(attached.len(), 1);
+
+  commands::delete(&db, &history, task.id, true, false)
+    .await
+    .unwrap();
+  assert!(checklist_of(&db, task.id).await.is_empty());
+  assert!(attachments_of(&db, task.id).await.is_empty());
+
+  let undone = history::step(&db, &history, true).await.unwrap().unwrap();
+  assert_eq!(undone.label, "delete_task");
+  assert_eq!(undone.created, vec![task.id]);
+  assert_eq!(checklist_of(&db, task.id).await, items);
+  assert_eq!(attachments_of(&db, task.id).await, attached);
+
+  history::step(&db, &history, false).await.unwrap().unwrap();
+  assert!(checklist_of(&db, task.id).await.is_empty());
+}
+
+#[tokio::test]
+async fn checklist_edits_can_be_undone() {
+  let (db, history) = memory_db().await;
+  let task = seed(&db, &history, 1).await.remove(0);
+  let item = checklist::add(&db, &history, task.id, "eggs")
+    .await
+    .unwrap();
+  assert_eq!(
+    checklist_of(&db, task.id).await,
+    vec![(item.id, "eggs".to_string(), false)]
+  );
+
+  let undone = history::step(&db, &history, true).await.unwrap().unwrap();
+  assert_eq!(undone.label, "add_checklist_item");
+  assert_eq!(undone.updated, vec![task.id]);
+  assert!(checklist_of(&db, task.id).await.is_empty());
+
+  history::step(&db, &history, false).await.unwrap().unwrap();
+  assert_eq!(
+    checklist_of(&db, task.id).await,
+    vec![(item.id, "eggs".to_string(), false)]
+  );
+}
+
+#[tokio::test]
+async fn attaching_a_file_can_be_undone() {
+  let (db, history) = memory_db().await;
+  let task = seed(&db, &history, 1).await.remove(0);
+  let file = attachable("attach");
+  let attachment = attachments::add(&db, &history, task.id, &file.0.to_string_lossy())
+    .await
+    .unwrap();
+
+  let undone = history::step(&db, &history, true).await.unwrap().unwrap();
+  assert_eq!(undone.label, "add_attachment");
+  assert!(attachments_of(&db, task.id).await.is_empty());
+
+  history::step(&db, &history, false).await.unwrap().unwrap();
+  assert_eq!(
+    attachments_of(&db, task.id).await,
+    vec![(attachment.id, attachment.file_name)]
+  );
+}
+
+#[tokio::test]
+async fn undoing_a_merge_gives_checklist_and_attachments_back() {
+  let (db, history) = memory_db().await;
+  let tasks = seed(&db, &history, 2).await;
+  let (keep, dupe) = (tasks[0].id, tasks[1].id);
+  let file = attachable("merge");
+  checklist::add(&db, &history, keep, "eggs").await.unwrap();
+  checklist::add(&db, &history, dupe, "flour").await.unwrap();
+  attachments::add(&db, &history, dupe, &file.0.to_string_lossy())
+    .await
+    .unwrap();
+  let kept_items = checklist_of(&db, keep).await;
+  let dupe_items = checklist_of(&db, dupe).await;
+  let dupe_attached = attachments_of(&db, dupe).await;
+
+  commands::merge(&db, &history, keep, vec![dupe])
+    .await
+    .unwrap();
+  assert_eq!(checklist_of(&db, keep).await.len(), 2);
+  assert_eq!(attachments_of(&db, keep).await, dupe_attached);
+  assert!(checklist_of(&db, dupe).await.is_empty());
+
+  let undone = history::step(&db, &history, true).await.unwrap().unwrap();
+  assert_eq!(undone.label, "merge_tasks");
+  assert_eq!(checklist_of(&db, keep).await, kept_items);
+  assert!(attachments_of(&db, keep).await.is_empty());
+  assert_eq!(checklist_of(&db, dupe).await, dupe_items);
+  assert_eq!(attachments_of(&db, dupe).await, dupe_attached);
+}
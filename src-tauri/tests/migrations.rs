@@ -0,0 +1,46 @@
+use app_lib::commands;
+use app_lib::db::AppDb;
+use app_lib::migrations;
+use app_lib::models::{SortBy, TaskFilter};
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use std::str::FromStr;
+
+#[tokio::test]
+async fn adds_created_at_and_due_to_a_legacy_tasks_table() {
+  let options = SqliteConnectOptions::from_str("sqlite::memory:")
+    .unwrap()
+    .foreign_keys(true);
+  let pool = SqlitePoolOptions::new()
+    .max_connections(1)
+    .idle_timeout(None)
+    .max_lifetime(None)
+    .connect_with(options)
+    .await
+    .unwrap();
+  // what the frontend created before either column existed
+  sqlx::query(
+    "CREATE TABLE tasks (
+       id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+       title               TEXT NOT NULL,
+       done                INTEGER NOT NULL DEFAULT 0,
+       list_id             INTEGER,
+       accumulated_seconds INTEGER NOT NULL DEFAULT 0,
+       running_since       TEXT NULL
+     );
+     INSERT INTO tasks (title) VALUES ('from an old build');",
+  )
+  .execute(&pool)
+  .await
+  .unwrap();
+
+  migrations::apply(&mut pool.acquire().await.unwrap())
+    .await
+    .unwrap();
+  let db = AppDb::new(pool);
+  let tasks = commands::list(&db, TaskFilter::All, SortBy::CreatedAsc, None, false)
+    .await
+    .unwrap();
+  assert_eq!(tasks.len(), 1);
+  assert_eq!(tasks[0].title, "from an old build");
+  assert_eq!(tasks[0].due, None);
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use app_lib::db::with_retry;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::ConnectOptions;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use common::scratch_path;
+
+#[tokio::test]
+async fn retries_while_another_connection_holds_the_lock() {
+  let scratch = scratch_path("retry-busy", "db");
+  // no busy timeout, so every attempt made under the lock fails at once
+  let options = SqliteConnectOptions::new()
+    .filename(&scratch.0)
+    .create_if_missing(true)
+    .busy_timeout(Duration::ZERO);
+  let mut holder = options.connect().await.unwrap();
+  sqlx::query("CREATE TABLE t (n INTEGER NOT NULL);")
+    .execute(&mut holder)
+    .await
+    .unwrap();
+  sqlx::query("BEGIN IMMEDIATE;")
+    .execute(&mut holder)
+    .await
+    .unwrap();
+  let pool = SqlitePoolOptions::new()
+    .max_connections(1)
+    .connect_with(options)
+    .await
+    .unwrap();
+
+  // released after the first retry's wait, but before the second's
+  let release = tokio::spawn(async move {
+    tokio::time::sleep(Duration::from_millis(75)).await;
+    sqlx::query("COMMIT;").execute(&mut holder).await.unwrap();
+  });
+  let attempts = AtomicU32::new(0);
+  let result = with_retry("test", || async {
+    attempts.fetch_add(1, Ordering::SeqCst);
+    let mut tx = pool.begin().await?;
+    sqlx::query("INSERT INTO t (n) VALUES (1);")
+      .execute(&mut *tx)
+      .await?;
+    tx.commit().await?;
+    Ok(())
+  })
+  .await;
+  release.await.unwrap();
+
+  assert_eq!(result, Ok(()));
+  assert!(attempts.load(Ordering::SeqCst) > 1);
+  let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM t;")
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+  assert_eq!(rows, 1);
+}
+
+#[tokio::test]
+async fn other_errors_are_not_retried() {
+  let pool = SqlitePoolOptions::new()
+    .connect("sqlite::memory:")
+    .await
+    .unwrap();
+
+  let attempts = AtomicU32::new(0);
+  let result = with_retry("test", || async {
+    attempts.fetch_add(1, Ordering::SeqCst);
+    sqlx::query("INSERT INTO missing (n) VALUES (1);")
+      .execute(&pool)
+      .await?;
+    Ok(())
+  })
+  .await;
+
+  assert!(result.is_err());
+  assert_eq!(attempts.load(Ordering::SeqCst), 1);
+}
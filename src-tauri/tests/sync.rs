@@ -0,0 +1,41 @@
+mod common;
+
+use app_lib::commands;
+use app_lib::sync;
+use common::{memory_db, seed};
+
+#[tokio::test]
+async fn changes_are_read_after_the_returned_cursor() {
+  let (db, history) = memory_db().await;
+  let tasks = seed(&db, &history, 3).await;
+  let everything = sync::changed_since(&db, 0).await.unwrap();
+  assert_eq!(everything.tasks.len(), 3);
+  assert!(everything.deleted.is_empty());
+
+  let unchanged = sync::changed_since(&db, everything.cursor).await.unwrap();
+  assert!(unchanged.tasks.is_empty() && unchanged.deleted.is_empty());
+  assert_eq!(unchanged.cursor, everything.cursor);
+
+  // the clock stepping back doesn't hide a write
+  sqlx::query(
+    "UPDATE tasks SET title = 'renamed', updated_at = '2000-01-01T00:00:00.000Z' WHERE id = ?;",
+  )
+  .bind(tasks[1].id)
+  .execute(&db.pool())
+  .await
+  .unwrap();
+  commands::delete(&db, &history, tasks[2].id, true, false)
+    .await
+    .unwrap();
+  let changed = sync::changed_since(&db, everything.cursor).await.unwrap();
+  assert_eq!(
+    changed.tasks.iter().map(|t| t.id).collect::<Vec<_>>(),
+    vec![tasks[1].id]
+  );
+  assert_eq!(changed.tasks[0].title, "renamed");
+  assert_eq!(changed.deleted, vec![tasks[2].id]);
+  assert!(changed.cursor > everything.cursor);
+
+  let after = sync::changed_since(&db, changed.cursor).await.unwrap();
+  assert!(after.tasks.is_empty() && after.deleted.is_empty());
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use app_lib::commands;
+use app_lib::transfer::{self, ImportMode};
+use common::{memory_db, seed};
+
+#[tokio::test]
+async fn list_membership_survives_export_and_import() {
+  let (source, history) = memory_db().await;
+  let tasks = seed(&source, &history, 3).await;
+  sqlx::query("INSERT INTO spaces (name) VALUES ('Home');")
+    .execute(&source.pool())
+    .await
+    .unwrap();
+  let groceries: i64 = sqlx::query_scalar(
+    "INSERT INTO lists (space_id, folder_id, name) VALUES (1, NULL, 'Groceries') RETURNING id;",
+  )
+  .fetch_one(&source.pool())
+  .await
+  .unwrap();
+  for task in &tasks[..2] {
+    sqlx::query("UPDATE tasks SET list_id = ? WHERE id = ?;")
+      .bind(groceries)
+      .bind(task.id)
+      .execute(&source.pool())
+      .await
+      .unwrap();
+  }
+  let json = transfer::export(&source).await.unwrap();
+
+  // a list of its own first, so "Groceries" gets a different id here
+  let (target, history) = memory_db().await;
+  sqlx::query("INSERT INTO spaces (name) VALUES ('Work');")
+    .execute(&target.pool())
+    .await
+    .unwrap();
+  sqlx::query("INSERT INTO lists (space_id, folder_id, name) VALUES (1, NULL, 'Errands');")
+    .execute(&target.pool())
+    .await
+    .unwrap();
+  let written = transfer::import(&target, &history, &json, ImportMode::Replace)
+    .await
+    .unwrap();
+  assert_eq!(written.len(), 3);
+
+  let memberships: Vec<(i64, Option<String>)> = sqlx::query_as(
+    "SELECT tasks.id, lists.name FROM tasks LEFT JOIN lists ON lists.id = tasks.list_id \
+     ORDER BY tasks.id;",
+  )
+  .fetch_all(&target.pool())
+  .await
+  .unwrap();
+  assert_eq!(
+    memberships,
+    vec![
+      (tasks[0].id, Some("Groceries".to_string())),
+      (tasks[1].id, Some("Groceries".to_string())),
+      (tasks[2].id, None),
+    ]
+  );
+}
+
+#[tokio::test]
+async fn replace_keeps_the_trash_and_brings_back_checklists_and_attachments() {
+  let (db, history) = memory_db().await;
+  let tasks = seed(&db, &history, 3).await;
+  for (text, position) in [("eggs", 0), ("flour", 1)] {
+    sqlx::query("INSERT INTO checklist_items (task_id, text, done, position) VALUES (?, ?, 0, ?);")
+      .bind(tasks[0].id)
+      .bind(text)
+      .bind(position)
+      .execute(&db.pool())
+      .await
+      .unwrap();
+  }
+  sqlx::query(
+    "INSERT INTO attachments (task_id, file_name, path, size, modified_at, attached_at) \
+     VALUES (?, 'recipe.pdf', '/home/me/recipe.pdf', 1024, NULL, '2024-05-01T09:00:00.000Z');",
+  )
+  .bind(tasks[0].id)
+  .execute(&db.pool())
+  .await
+  .unwrap();
+  commands::delete(&db, &history, tasks[2].id, false, false)
+    .await
+    .unwrap();
+  let json = transfer::export(&db).await.unwrap();
+
+  // edits after the export are what Replace throws away
+  sqlx::query("DELETE FROM checklist_items;")
+    .execute(&db.pool())
+    .await
+    .unwrap();
+  sqlx::query("DELETE FROM attachments;")
+    .execute(&db.pool())
+    .await
+    .unwrap();
+  let written = transfer::import(&db, &history, &json, ImportMode::Replace)
+    .await
+    .unwrap();
+  assert_eq!(written, vec![tasks[0].id, tasks[1].id]);
+
+  let trashed: Vec<i64> = sqlx::query_scalar("SELECT id FROM tasks WHERE deleted_at IS NOT NULL;")
+    .fetch_all(&db.pool())
+    .await
+    .unwrap();
+  assert_eq!(trashed, vec![tasks[2].id]);
+  let checklist: Vec<(String, i64)> = sqlx::query_as(
+    "SELECT text, position FROM checklist_items WHERE task_id = ? ORDER BY position;",
+  )
+  .bind(tasks[0].id)
+  .fetch_all(&db.pool())
+  .await
+  .unwrap();
+  assert_eq!(
+    checklist,
+    vec![("eggs".to_string(), 0), ("flour".to_string(), 1)]
+  );
+  let attached: Vec<(i64, String)> = sqlx::query_as("SELECT task_id, path FROM attachments;")
+    .fetch_all(&db.pool())
+    .await
+    .unwrap();
+  assert_eq!(
+    attached,
+    vec![(tasks[0].id, "/home/me/recipe.pdf".to_string())]
+  );
+}
+
+#[tokio::test]
+async fn csv_rows_only_update_the_live_task_they_came_from() {
+  let (db, history) = memory_db().await;
+  let tasks = seed(&db, &history, 2).await;
+  let created_at: Vec<String> = sqlx::query_scalar("SELECT created_at FROM tasks ORDER BY id;")
+    .fetch_all(&db.pool())
+    .await
+    .unwrap();
+  commands::delete(&db, &history, tasks[1].id, false, false)
+    .await
+    .unwrap();
+
+  let csv = format!(
+    "id,title,created_at\n\
+     {kept},renamed,{kept_at}\n\
+     {trashed},revived?,{trashed_at}\n\
+     {kept},same id elsewhere,2020-01-01T00:00:00.000Z\n\
+     999,from another database,{kept_at}\n",
+    kept = tasks[0].id,
+    kept_at = created_at[0],
+    trashed = tasks[1].id,
+    trashed_at = created_at[1],
+  );
+  let (written, errors) = transfer::import_csv(&db, &history, &csv).await.unwrap();
+  assert!(errors.is_empty(), "{errors:?}");
+  assert_eq!(written.len(), 4);
+  assert_eq!(written[0], tasks[0].id);
+  assert!(!written.contains(&999));
+
+  let rows: Vec<(i64, String, bool)> =
+    sqlx::query_as("SELECT id, title, deleted_at IS NOT NULL FROM tasks ORDER BY id;")
+      .fetch_all(&db.pool())
+      .await
+      .unwrap();
+  assert_eq!(
+    rows,
+    vec![
+      (tasks[0].id, "renamed".to_string(), false),
+      (tasks[1].id, "task 2".to_string(), true),
+      (written[1], "revived?".to_string(), false),
+      (written[2], "same id elsewhere".to_string(), false),
+      (written[3], "from another database".to_string(), false),
+    ]
+  );
+}
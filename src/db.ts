@@ -1,12 +1,18 @@
 // src/db.ts
 import Database from "@tauri-apps/plugin-sql";
+import { invoke } from "@tauri-apps/api/core";
 import type { Space, Folder, List, Task } from "./types";
 
 let dbPromise: Promise<Database> | null = null;
 
 export async function getDb(): Promise<Database> {
   if (!dbPromise) {
-    dbPromise = Database.load("sqlite:tasks.db");
+    // The database lives in the OS app-data dir; Rust owns the exact path and
+    // has already opened it for the plugin (with the key, if it's encrypted),
+    // so `get` rather than `load`, which would open it again without one.
+    dbPromise = unlock()
+      .then(() => invoke<string>("db_url"))
+      .then(url => Database.get(url));
     const db = await dbPromise;
 
     // Always enforce FK integrity
@@ -62,16 +68,9 @@ export async function getDb(): Promise<Database> {
     await db.execute(`CREATE INDEX IF NOT EXISTS idx_lists_space  ON lists(space_id);`);
     await db.execute(`CREATE INDEX IF NOT EXISTS idx_lists_folder ON lists(folder_id);`);
 
-    await db.execute(`
-      CREATE TABLE IF NOT EXISTS tasks (
-        id       INTEGER PRIMARY KEY AUTOINCREMENT,
-        title    TEXT NOT NULL,
-        done     INTEGER NOT NULL DEFAULT 0,
-        list_id  INTEGER REFERENCES lists(id) ON DELETE CASCADE,
-        accumulated_seconds INTEGER NOT NULL DEFAULT 0,
-        running_since TEXT NULL
-      );
-    `);
+    // The tasks table itself is created by the Rust migrations
+    // (src-tauri/src/migrations.rs), which run before the window loads and
+    // also add "created_at"/"due" to tasks tables from older builds.
 
       // ---- Migrations: add "position" to folders/lists if missing ----
       const folderCols = await db.select<{ name: string }[]>(`PRAGMA table_info(folders);`);
@@ -120,6 +119,24 @@ export async function getDb(): Promise<Database> {
   return dbPromise;
 }
 
+// ---------- Encryption ----------
+// An encrypted database stays closed until Rust has its passphrase.
+async function unlock(): Promise<void> {
+  if (!(await invoke<boolean>("is_db_locked"))) return;
+  let prompt = "Your tasks are encrypted. Enter the passphrase:";
+  for (;;) {
+    const passphrase = window.prompt(prompt);
+    if (passphrase === null) throw new Error("the database is locked");
+    try {
+      await invoke("set_db_key", { passphrase });
+      return;
+    } catch (e) {
+      if ((e as { kind?: string }).kind !== "invalid_passphrase") throw e;
+      prompt = "That passphrase is not right. Try again:";
+    }
+  }
+}
+
 // ---------- Defaults ----------
 async function ensureDefaultSpace(db: Database): Promise<number> {
   const row = await db.select<{ id: number }[]>(
@@ -289,7 +306,7 @@ export async function getTasks(listId: number): Promise<Task[]> {
   const db = await getDb();
   return db.select<Task[]>(
     `SELECT id, list_id, title, done, accumulated_seconds, running_since
-     FROM tasks WHERE list_id = ? ORDER BY id DESC;`,
+     FROM tasks WHERE list_id = ? AND deleted_at IS NULL ORDER BY id DESC;`,
     [listId]
   );
 }
@@ -297,9 +314,9 @@ export async function getTasks(listId: number): Promise<Task[]> {
 export async function addTask(listId: number, title: string): Promise<void> {
   const db = await getDb();
   await db.execute(
-    `INSERT INTO tasks (list_id, title, done, accumulated_seconds, running_since)
-     VALUES (?, ?, 0, 0, NULL);`,
-    [listId, title]
+    `INSERT INTO tasks (list_id, title, done, accumulated_seconds, running_since, created_at)
+     VALUES (?, ?, 0, 0, NULL, ?);`,
+    [listId, title, new Date().toISOString()]
   );
 }
 